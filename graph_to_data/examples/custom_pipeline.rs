@@ -0,0 +1,160 @@
+use graph_to_data::{
+    AggregationStage, AxesMapping, Axis, Centroid, CombinedVerticals, Crop, CropMode, CropStage,
+    Error, GraphMultiNode, GroupingStage, Mapping, MappingStage, MaskStage, MultiNode,
+    PaletteColor, PaletteStage, Pipeline, Settings, StitchingStage, UnitQuadrilateral,
+};
+
+/// The whole image without straightening, one step per pixel
+struct WholeImage;
+impl CropStage for WholeImage {
+    fn crop(
+        &self,
+        image: &image::RgbaImage,
+        _quadrilateral: UnitQuadrilateral,
+        _axes: &AxesMapping,
+        _settings: &Settings,
+        _steps_x: u32,
+        _steps_y: u32,
+    ) -> Result<Crop, Error> {
+        Crop::new(
+            UnitQuadrilateral::unit_square(),
+            CropMode::Bilinear,
+            image.width(),
+            image.height(),
+        )
+    }
+}
+
+/// Known curve colors instead of clustering
+struct KnownColors(Vec<image::Rgba<u8>>);
+impl PaletteStage for KnownColors {
+    fn extract_colors(&self, _image: &image::RgbaImage, _settings: &Settings) -> Vec<PaletteColor> {
+        self.0
+            .iter()
+            .map(|&color| PaletteColor {
+                color,
+                pixel_count: 0,
+            })
+            .collect()
+    }
+}
+
+/// Pixels close to the color in each channel
+struct ChannelMask;
+impl MaskStage for ChannelMask {
+    fn mask(
+        &self,
+        image: &image::RgbaImage,
+        color: &image::Rgba<u8>,
+        settings: &Settings,
+    ) -> image::GrayImage {
+        let radius = settings.step1_step2_color_radius as i16;
+        image::GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            let pixel = image.get_pixel(x, y);
+            let close = (0..3).all(|i| (pixel[i] as i16 - color[i] as i16).abs() <= radius);
+            if close {
+                graph_to_data::HIT
+            } else {
+                graph_to_data::MISSED
+            }
+        })
+    }
+}
+
+/// One graph with the first run of each column, for plots with a single curve
+struct FirstRun;
+impl GroupingStage for FirstRun {
+    fn group(
+        &self,
+        mask: &image::GrayImage,
+        _image: &image::RgbaImage,
+        _color: &image::Rgba<u8>,
+        _settings: &Settings,
+    ) -> (Vec<GraphMultiNode>, Vec<CombinedVerticals>) {
+        let mut graph = GraphMultiNode::empty(mask.width() as usize);
+        for x in 0..mask.width() {
+            let is_hit = |y: u32| mask[(x, y)] == graph_to_data::HIT;
+            let Some(y_min) = (0..mask.height()).find(|&y| is_hit(y)) else {
+                continue;
+            };
+            let y_max = (y_min..mask.height())
+                .take_while(|&y| is_hit(y))
+                .last()
+                .unwrap_or(y_min);
+            let weight = (y_max - y_min + 1) as f32;
+            let centroid = Centroid::new(weight, (y_min + y_max) as f32 / 2.);
+            graph.ys[x as usize] = MultiNode::from_run(y_min, y_max, centroid);
+        }
+        (vec![graph], Vec::new())
+    }
+}
+
+/// The default stitching without bridging dashes
+struct StitchOnly;
+impl StitchingStage for StitchOnly {
+    fn stitch(
+        &self,
+        components: Vec<GraphMultiNode>,
+        remaining: &mut Vec<CombinedVerticals>,
+        mask: &image::GrayImage,
+        settings: &Settings,
+    ) -> Vec<GraphMultiNode> {
+        graph_to_data::stitch(components, remaining, settings, mask)
+    }
+}
+
+/// Every graph becomes a curve of its own
+struct KeepAll;
+impl AggregationStage for KeepAll {
+    fn aggregate(&self, graphs: Vec<GraphMultiNode>, _settings: &Settings) -> Vec<GraphMultiNode> {
+        graphs
+    }
+}
+
+/// Pixel positions relative to the bottom left corner
+struct Pixels {
+    height: u32,
+}
+impl Mapping for Pixels {
+    fn map(&self, x: f32, y: f32) -> (f64, f64) {
+        (x as f64, (self.height as f32 - 1. - y) as f64)
+    }
+}
+struct PixelMapping;
+impl MappingStage for PixelMapping {
+    fn prepare(&self, _axes: &AxesMapping, crop: &Crop) -> Result<Box<dyn Mapping>, Error> {
+        Ok(Box::new(Pixels {
+            height: crop.steps_y(),
+        }))
+    }
+}
+
+fn main() {
+    let image_bytes = include_bytes!("../../Readme_Image_Graph.png");
+    let image = image::load_from_memory(image_bytes).unwrap().to_rgba8();
+
+    let pipeline = Pipeline {
+        crop: Box::new(WholeImage),
+        palette: Box::new(KnownColors(vec![image::Rgba([0, 69, 134, 255])])),
+        mask: Box::new(ChannelMask),
+        grouping: Box::new(FirstRun),
+        stitching: Box::new(StitchOnly),
+        aggregation: Box::new(KeepAll),
+        mapping: Box::new(PixelMapping),
+        ..Default::default()
+    };
+    let lines = pipeline
+        .line_detection(
+            &image,
+            &Default::default(),
+            UnitQuadrilateral::unit_square(),
+            image.width(),
+            image.height(),
+            &AxesMapping::Limits {
+                x_axis: Axis::linear(0., 1.),
+                y_axis: Axis::linear(0., 1.),
+            },
+        )
+        .unwrap();
+    println!("{}", lines.as_csv());
+}
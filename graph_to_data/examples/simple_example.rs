@@ -1,11 +1,6 @@
 fn main() {
     let image_bytes = include_bytes!("../../Readme_Image_Graph.png");
-    let image = image::io::Reader::new(std::io::Cursor::new(image_bytes))
-        .with_guessed_format()
-        .unwrap()
-        .decode()
-        .unwrap()
-        .to_rgba8();
+    let image = image::load_from_memory(image_bytes).unwrap().to_rgba8();
 
     let lines = graph_to_data::line_detection(
         &image,
@@ -13,8 +8,10 @@ fn main() {
         graph_to_data::UnitQuadrilateral::unit_square(),
         image.width(),
         image.height(),
-        (1950., 2010.),
-        (0., 60.),
+        &graph_to_data::AxesMapping::Limits {
+            x_axis: graph_to_data::Axis::linear(1950., 2010.),
+            y_axis: graph_to_data::Axis::linear(0., 60.),
+        },
     )
     .unwrap();
     lines.save("").unwrap();
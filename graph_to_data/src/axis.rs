@@ -0,0 +1,365 @@
+use crate::{
+    step0_crop::{Crop, CropMode},
+    Calibration, CalibrationFit, Error, PolarCalibration, UnitQuadrilateral,
+};
+
+/// Relation between pixel position and data value along an axis
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AxisScale {
+    #[default]
+    Linear,
+    Log10,
+    Ln,
+    Log {
+        base: f64,
+    },
+}
+impl AxisScale {
+    pub fn is_logarithmic(&self) -> bool {
+        !matches!(self, AxisScale::Linear)
+    }
+
+    /// Data value to position on a linear axis
+    pub(crate) fn forward(&self, value: f64) -> f64 {
+        match self {
+            AxisScale::Linear => value,
+            AxisScale::Log10 => value.log10(),
+            AxisScale::Ln => value.ln(),
+            AxisScale::Log { base } => value.log(*base),
+        }
+    }
+
+    /// Position on a linear axis to data value
+    pub(crate) fn backward(&self, value: f64) -> f64 {
+        match self {
+            AxisScale::Linear => value,
+            AxisScale::Log10 => 10f64.powf(value),
+            AxisScale::Ln => value.exp(),
+            AxisScale::Log { base } => base.powf(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum TimeUnit {
+    Milliseconds,
+    Seconds,
+    Minutes,
+    Hours,
+    #[default]
+    Days,
+}
+impl TimeUnit {
+    fn seconds(&self) -> f64 {
+        match self {
+            TimeUnit::Milliseconds => 1e-3,
+            TimeUnit::Seconds => 1.,
+            TimeUnit::Minutes => 60.,
+            TimeUnit::Hours => 3600.,
+            TimeUnit::Days => 86400.,
+        }
+    }
+}
+
+/// How values of an axis are entered and written, the data values themselves are numbers
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AxisFormat {
+    #[default]
+    Number,
+    /// Values count units since 1970-01-01T00:00:00Z and are written as ISO 8601 timestamps in
+    /// the timezone, which is also used for timestamps without an offset
+    Time {
+        unit: TimeUnit,
+        utc_offset_minutes: i32,
+    },
+}
+impl AxisFormat {
+    /// Accepts an RFC 3339 timestamp, a date and time without an offset or a date
+    pub fn parse(&self, text: &str) -> Result<f64, String> {
+        let text = text.trim();
+        let AxisFormat::Time {
+            unit,
+            utc_offset_minutes,
+        } = self
+        else {
+            return text
+                .parse()
+                .map_err(|e: std::num::ParseFloatError| e.to_string());
+        };
+        let timestamp = if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(text) {
+            timestamp.naive_utc()
+        } else {
+            let local = [
+                "%Y-%m-%dT%H:%M:%S%.f",
+                "%Y-%m-%dT%H:%M",
+                "%Y-%m-%d %H:%M:%S%.f",
+                "%Y-%m-%d %H:%M",
+            ]
+            .iter()
+            .find_map(|format| chrono::NaiveDateTime::parse_from_str(text, format).ok())
+            .or_else(|| {
+                chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                    .ok()
+                    .map(|date| date.and_time(chrono::NaiveTime::MIN))
+            })
+            .ok_or_else(|| format!("'{text}' is no ISO 8601 date or timestamp"))?;
+            local - chrono::TimeDelta::minutes(*utc_offset_minutes as i64)
+        };
+        let timestamp = timestamp.and_utc();
+        let since_epoch =
+            timestamp.timestamp() as f64 + timestamp.timestamp_subsec_nanos() as f64 * 1e-9;
+        Ok(since_epoch / unit.seconds())
+    }
+
+    /// Timestamps are rounded to milliseconds
+    pub fn format(&self, value: f64) -> String {
+        let AxisFormat::Time {
+            unit,
+            utc_offset_minutes,
+        } = self
+        else {
+            return value.to_string();
+        };
+        let milliseconds = (value * unit.seconds() * 1e3).round();
+        let timestamp = (milliseconds.is_finite() && milliseconds.abs() < i64::MAX as f64)
+            .then(|| chrono::DateTime::from_timestamp_millis(milliseconds as i64))
+            .flatten();
+        let offset = chrono::FixedOffset::east_opt(utc_offset_minutes * 60);
+        match (timestamp, offset) {
+            (Some(timestamp), Some(offset)) => timestamp
+                .with_timezone(&offset)
+                .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            _ => value.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Axis {
+    pub min: f64,
+    pub max: f64,
+    pub scale: AxisScale,
+    #[serde(default)]
+    pub format: AxisFormat,
+}
+impl Axis {
+    pub fn linear(min: f64, max: f64) -> Self {
+        Self {
+            min,
+            max,
+            scale: AxisScale::Linear,
+            format: AxisFormat::Number,
+        }
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        let Self {
+            min, max, scale, ..
+        } = *self;
+        // reversed axes, i.e. max < min, are fine
+        if !(min.is_finite() && max.is_finite()) || min == max {
+            return Err(Error::AxisLimitsInvalid { min, max });
+        }
+        if let AxisScale::Log { base } = scale {
+            if !(base.is_finite() && base > 0. && base != 1.) {
+                return Err(Error::AxisLogBaseInvalid { base });
+            }
+        }
+        if scale.is_logarithmic() && (min <= 0. || max <= 0.) {
+            return Err(Error::AxisLimitsNotPositive { min, max });
+        }
+        Ok(())
+    }
+
+    /// Maps a fraction between 0. (axis minimum) and 1. (axis maximum) to a data value
+    pub(crate) fn interpolate(&self, fraction: f64) -> f64 {
+        let Self {
+            min, max, scale, ..
+        } = self;
+        let min = scale.forward(*min);
+        let max = scale.forward(*max);
+        scale.backward(min + fraction * (max - min))
+    }
+}
+
+/// How positions in the image are converted to data values
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub enum AxesMapping {
+    /// The edges of the crop quadrilateral correspond to the axis limits
+    Limits {
+        x_axis: Axis,
+        y_axis: Axis,
+    },
+    Calibration(Calibration),
+    /// The crop quadrilateral is ignored, the image is unwrapped around the center instead
+    Polar(PolarCalibration),
+}
+impl AxesMapping {
+    /// Formats of the x and y values, angles of polar axes are numbers
+    pub fn formats(&self) -> [AxisFormat; 2] {
+        match self {
+            AxesMapping::Limits { x_axis, y_axis } => [x_axis.format, y_axis.format],
+            AxesMapping::Calibration(calibration) => {
+                [calibration.x_axis.format, calibration.y_axis.format]
+            }
+            AxesMapping::Polar(polar) => [AxisFormat::Number, polar.radius.format],
+        }
+    }
+
+    pub(crate) fn crop(
+        &self,
+        quadrilateral: UnitQuadrilateral,
+        mode: CropMode,
+        dimensions: [u32; 2],
+        steps_x: u32,
+        steps_y: u32,
+    ) -> Result<Crop, Error> {
+        match self {
+            AxesMapping::Polar(polar) => Crop::polar(polar, dimensions, steps_x, steps_y),
+            AxesMapping::Limits { .. } | AxesMapping::Calibration(_) => {
+                Crop::new(quadrilateral, mode, steps_x, steps_y)
+            }
+        }
+    }
+
+    pub(crate) fn prepare(&self, crop: &Crop) -> Result<PixelMapping, Error> {
+        let kind = match self {
+            AxesMapping::Limits { x_axis, y_axis } => {
+                x_axis.check()?;
+                y_axis.check()?;
+                PixelMappingKind::Limits {
+                    x_axis: *x_axis,
+                    y_axis: *y_axis,
+                }
+            }
+            AxesMapping::Calibration(calibration) => {
+                PixelMappingKind::Calibration(calibration.fit()?)
+            }
+            AxesMapping::Polar(polar) => PixelMappingKind::Polar(polar.clone()),
+        };
+        Ok(PixelMapping {
+            kind,
+            crop: crop.clone(),
+        })
+    }
+}
+
+enum PixelMappingKind {
+    Limits { x_axis: Axis, y_axis: Axis },
+    Calibration(CalibrationFit),
+    Polar(PolarCalibration),
+}
+/// Converts pixels of the cropped image to data values
+pub(crate) struct PixelMapping {
+    kind: PixelMappingKind,
+    crop: Crop,
+}
+impl PixelMapping {
+    pub(crate) fn map(&self, x: f32, y: f32) -> (f64, f64) {
+        let Self { kind, crop } = self;
+        match kind {
+            PixelMappingKind::Limits { x_axis, y_axis } => {
+                let t = |x: f32, n: u32| (x as f64 + 1.) / (n + 1) as f64;
+                let x = x_axis.interpolate(t(x, crop.steps_x()));
+                let y = y_axis.interpolate(1. - t(y, crop.steps_y()));
+                (x, y)
+            }
+            PixelMappingKind::Calibration(fit) => fit.map(crop.source_position(x, y)),
+            PixelMappingKind::Polar(polar) => polar.map(x, y, [crop.steps_x(), crop.steps_y()]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axis(min: f64, max: f64, scale: AxisScale) -> Axis {
+        Axis {
+            scale,
+            ..Axis::linear(min, max)
+        }
+    }
+
+    #[test]
+    fn scales_invert() {
+        for scale in [
+            AxisScale::Linear,
+            AxisScale::Log10,
+            AxisScale::Ln,
+            AxisScale::Log { base: 2. },
+        ] {
+            for value in [0.5, 1., 42., 1e6] {
+                let inverted = scale.backward(scale.forward(value));
+                assert!((inverted - value).abs() < 1e-9 * value, "{scale:?} {value}");
+            }
+        }
+        assert_eq!(AxisScale::Log10.forward(1000.), 3.);
+        assert_eq!(AxisScale::Log { base: 2. }.forward(8.), 3.);
+    }
+
+    #[test]
+    fn log_axes_interpolate_geometrically() {
+        let decades = axis(1., 100., AxisScale::Log10);
+        assert!((decades.interpolate(0.5) - 10.).abs() < 1e-9);
+        assert!((decades.interpolate(1.) - 100.).abs() < 1e-9);
+        let reversed = axis(100., 1., AxisScale::Log10);
+        assert!((reversed.interpolate(0.) - 100.).abs() < 1e-9);
+        assert!((reversed.interpolate(0.5) - 10.).abs() < 1e-9);
+        assert!((reversed.interpolate(0.75) - 10f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn log_axes_need_positive_limits_and_a_valid_base() {
+        assert!(axis(1., 1000., AxisScale::Log10).check().is_ok());
+        assert!(axis(1000., 1., AxisScale::Ln).check().is_ok());
+        assert!(axis(-5., 5., AxisScale::Linear).check().is_ok());
+        assert!(matches!(
+            axis(0., 10., AxisScale::Log10).check(),
+            Err(Error::AxisLimitsNotPositive { .. })
+        ));
+        assert!(matches!(
+            axis(10., -1., AxisScale::Ln).check(),
+            Err(Error::AxisLimitsNotPositive { .. })
+        ));
+        for base in [1., 0., -2., f64::NAN] {
+            assert!(matches!(
+                axis(1., 10., AxisScale::Log { base }).check(),
+                Err(Error::AxisLogBaseInvalid { .. })
+            ));
+        }
+        assert!(matches!(
+            axis(3., 3., AxisScale::Linear).check(),
+            Err(Error::AxisLimitsInvalid { .. })
+        ));
+    }
+
+    #[test]
+    fn timestamps_round_trip_in_the_timezone() {
+        let format = AxisFormat::Time {
+            unit: TimeUnit::Hours,
+            utc_offset_minutes: 120,
+        };
+        let value = format.parse("2024-03-01T12:00:00+02:00").unwrap();
+        assert_eq!(value, format.parse("2024-03-01T10:00:00Z").unwrap());
+        assert_eq!(format.format(value), "2024-03-01T12:00:00+02:00");
+        // timestamps without an offset are in the timezone
+        assert_eq!(format.parse("2024-03-01 12:00").unwrap(), value);
+        assert_eq!(format.parse("2024-03-01").unwrap(), value - 12.);
+        assert!(format.parse("March 2024").is_err());
+
+        let seconds = AxisFormat::Time {
+            unit: TimeUnit::Seconds,
+            utc_offset_minutes: -330,
+        };
+        let text = "1999-12-31T23:59:59.250-05:30";
+        assert_eq!(seconds.format(seconds.parse(text).unwrap()), text);
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        assert_eq!(AxisFormat::Number.parse(" 1.5 "), Ok(1.5));
+        assert_eq!(AxisFormat::Number.format(1.5), "1.5");
+        assert!(AxisFormat::Number.parse("2024-03-01").is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{AxisFormat, AxisScale, Error, UnitPoint};
+
+/// Known position in the (uncropped) image together with its data value
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferencePoint {
+    pub position: UnitPoint,
+    pub value: f64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct AxisCalibration {
+    pub scale: AxisScale,
+    pub points: Vec<ReferencePoint>,
+    #[serde(default)]
+    pub format: AxisFormat,
+}
+impl AxisCalibration {
+    /// Least squares fit of 'position = origin + scaled value * direction'
+    fn fit(&self) -> Result<AxisFit, Error> {
+        let Self { scale, points, .. } = self;
+        if points.len() < 2 {
+            return Err(Error::CalibrationInsufficientPoints {
+                count: points.len(),
+            });
+        }
+        let mut samples = Vec::with_capacity(points.len());
+        for ReferencePoint { position, value } in points {
+            if scale.is_logarithmic() && *value <= 0. {
+                return Err(Error::CalibrationValueNotPositive { value: *value });
+            }
+            samples.push((
+                scale.forward(*value),
+                [position.x.0 as f64, position.y.0 as f64],
+            ));
+        }
+        let n = samples.len() as f64;
+        let mean_s = samples.iter().map(|(s, _)| s).sum::<f64>() / n;
+        let mean_p = [0, 1].map(|i| samples.iter().map(|(_, p)| p[i]).sum::<f64>() / n);
+        let variance = samples
+            .iter()
+            .map(|(s, _)| (s - mean_s).powi(2))
+            .sum::<f64>();
+        if !(variance.is_finite() && variance > 0.) {
+            return Err(Error::CalibrationDegenerate);
+        }
+        let direction = [0, 1].map(|i| {
+            samples
+                .iter()
+                .map(|(s, p)| (s - mean_s) * (p[i] - mean_p[i]))
+                .sum::<f64>()
+                / variance
+        });
+        let origin = [0, 1].map(|i| mean_p[i] - mean_s * direction[i]);
+        Ok(AxisFit {
+            scale: *scale,
+            origin,
+            direction,
+        })
+    }
+}
+
+/// Axis calibration via reference points, e.g. tick marks
+/// The axes may be rotated or skewed, i.e. the mapping is affine
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Calibration {
+    pub x_axis: AxisCalibration,
+    pub y_axis: AxisCalibration,
+}
+impl Calibration {
+    pub fn fit(&self) -> Result<CalibrationFit, Error> {
+        let x = self.x_axis.fit()?;
+        let y = self.y_axis.fit()?;
+        let determinant = cross(x.direction, y.direction);
+        if !(determinant.is_finite() && determinant.abs() > f64::EPSILON) {
+            return Err(Error::CalibrationDegenerate);
+        }
+        Ok(CalibrationFit { x, y, determinant })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AxisFit {
+    scale: AxisScale,
+    origin: [f64; 2],
+    direction: [f64; 2],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CalibrationFit {
+    x: AxisFit,
+    y: AxisFit,
+    determinant: f64,
+}
+impl CalibrationFit {
+    /// Maps a position in the (uncropped) image to data values
+    pub fn map(&self, position: [f32; 2]) -> (f64, f64) {
+        let Self { x, y, determinant } = self;
+        let position = position.map(f64::from);
+        // x value is constant along lines parallel to the y-axis and vice versa
+        let u = cross(sub(position, x.origin), y.direction) / determinant;
+        let w = cross(sub(position, y.origin), x.direction) / -determinant;
+        (x.scale.backward(u), y.scale.backward(w))
+    }
+}
+
+fn cross(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(position: [f32; 2], value: f64) -> ReferencePoint {
+        ReferencePoint {
+            position: UnitPoint::new(position).unwrap(),
+            value,
+        }
+    }
+
+    fn axis(scale: AxisScale, points: &[([f32; 2], f64)]) -> AxisCalibration {
+        AxisCalibration {
+            scale,
+            points: points.iter().map(|&(p, v)| point(p, v)).collect(),
+            format: AxisFormat::Number,
+        }
+    }
+
+    /// Position of data values on rotated and skewed axes
+    fn position(x: f64, y: f64) -> [f32; 2] {
+        [
+            (0.2 + 0.006 * x + 0.001 * y) as f32,
+            (0.8 + 0.002 * x - 0.007 * y) as f32,
+        ]
+    }
+
+    #[test]
+    fn fit_round_trip() {
+        let calibration = Calibration {
+            x_axis: axis(
+                AxisScale::Linear,
+                &[
+                    (position(0., 0.), 0.),
+                    (position(50., 0.), 50.),
+                    (position(100., 0.), 100.),
+                ],
+            ),
+            y_axis: axis(
+                AxisScale::Linear,
+                &[(position(0., 0.), 0.), (position(0., 80.), 80.)],
+            ),
+        };
+        let fit = calibration.fit().unwrap();
+        for (x, y) in [(0., 0.), (25., 60.), (100., 80.), (-10., 5.)] {
+            let (mapped_x, mapped_y) = fit.map(position(x, y));
+            assert!((mapped_x - x).abs() < 1e-3, "{mapped_x} != {x}");
+            assert!((mapped_y - y).abs() < 1e-3, "{mapped_y} != {y}");
+        }
+    }
+
+    #[test]
+    fn fit_round_trip_logarithmic() {
+        let calibration = Calibration {
+            x_axis: axis(AxisScale::Linear, &[([0.1, 0.9], 0.), ([0.9, 0.9], 8.)]),
+            y_axis: axis(
+                AxisScale::Log10,
+                &[([0.1, 0.9], 1.), ([0.1, 0.5], 10.), ([0.1, 0.1], 100.)],
+            ),
+        };
+        let fit = calibration.fit().unwrap();
+        let (x, y) = fit.map([0.5, 0.3]);
+        assert!((x - 4.).abs() < 1e-5);
+        assert!((y - 10f64.powf(1.5)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn least_squares_averages_noise() {
+        // errors uncorrelated with the values cancel out
+        let x_axis = axis(
+            AxisScale::Linear,
+            &[
+                ([0.11, 0.9], 0.),
+                ([0.29, 0.9], 1.),
+                ([0.49, 0.9], 2.),
+                ([0.71, 0.9], 3.),
+            ],
+        );
+        let fit = x_axis.fit().unwrap();
+        assert!((fit.direction[0] - 0.2).abs() < 1e-6);
+        assert!(fit.direction[1].abs() < 1e-6);
+        assert!((fit.origin[0] - 0.1).abs() < 1e-6);
+        assert!((fit.origin[1] - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn insufficient_points() {
+        let calibration = Calibration {
+            x_axis: axis(AxisScale::Linear, &[([0.1, 0.9], 0.)]),
+            y_axis: axis(AxisScale::Linear, &[([0.1, 0.9], 0.), ([0.1, 0.1], 1.)]),
+        };
+        assert!(matches!(
+            calibration.fit(),
+            Err(Error::CalibrationInsufficientPoints { count: 1 })
+        ));
+        assert!(matches!(
+            AxisCalibration::default().fit(),
+            Err(Error::CalibrationInsufficientPoints { count: 0 })
+        ));
+    }
+
+    #[test]
+    fn degenerate() {
+        // all reference points with the same value
+        let same_value = axis(AxisScale::Linear, &[([0.1, 0.9], 1.), ([0.9, 0.9], 1.)]);
+        assert!(matches!(
+            same_value.fit(),
+            Err(Error::CalibrationDegenerate)
+        ));
+        // parallel axes
+        let calibration = Calibration {
+            x_axis: axis(AxisScale::Linear, &[([0.1, 0.9], 0.), ([0.9, 0.9], 1.)]),
+            y_axis: axis(AxisScale::Linear, &[([0.1, 0.5], 0.), ([0.9, 0.5], 1.)]),
+        };
+        assert!(matches!(
+            calibration.fit(),
+            Err(Error::CalibrationDegenerate)
+        ));
+    }
+
+    #[test]
+    fn logarithmic_value_not_positive() {
+        let log = axis(AxisScale::Log10, &[([0.1, 0.9], 0.), ([0.9, 0.9], 10.)]);
+        assert!(matches!(
+            log.fit(),
+            Err(Error::CalibrationValueNotPositive { .. })
+        ));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{color_distance, color_distance_three};
+
+/// How the difference of two colors is measured, the color radius is given in the same unit
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorMetric {
+    /// Sum of the absolute channel differences
+    #[default]
+    Rgb,
+    /// CIEDE2000 color difference in CIELAB, close to the perceived difference
+    Cielab,
+}
+impl ColorMetric {
+    pub(crate) fn distance(&self, cc: &image::Rgba<u8>, c: &image::Rgba<u8>) -> f32 {
+        match self {
+            ColorMetric::Rgb => color_distance(cc, c) as f32,
+            ColorMetric::Cielab => Lab::from_rgb(cc.0).delta_e_2000(&Lab::from_rgb(c.0)),
+        }
+    }
+
+    pub(crate) fn distance_to_rgb(&self, cc: &image::Rgb<u8>, c: &image::Rgba<u8>) -> f32 {
+        match self {
+            ColorMetric::Rgb => color_distance_three(cc, c) as f32,
+            ColorMetric::Cielab => Lab::from_rgb(cc.0).delta_e_2000(&Lab::from_rgb(c.0)),
+        }
+    }
+}
+
+/// CIELAB color with D65 white point
+#[derive(Debug, Clone, Copy)]
+struct Lab {
+    l: f32,
+    a: f32,
+    b: f32,
+}
+impl Lab {
+    fn from_rgb(rgb: impl AsRef<[u8]>) -> Self {
+        let rgb = rgb.as_ref();
+        let [r, g, b] = [rgb[0], rgb[1], rgb[2]].map(|c| {
+            let c = c as f32 / 255.;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+        let f = |t: f32| {
+            if t > 0.008856 {
+                t.cbrt()
+            } else {
+                7.787 * t + 16. / 116.
+            }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        Self {
+            l: 116. * fy - 16.,
+            a: 500. * (fx - fy),
+            b: 200. * (fy - fz),
+        }
+    }
+
+    /// Formula of Sharma, Wu and Dalal (2005)
+    fn delta_e_2000(&self, other: &Lab) -> f32 {
+        const POW_25_7: f32 = 6103515625.;
+        let hue = |b: f32, a: f32| {
+            if a == 0. && b == 0. {
+                0.
+            } else {
+                b.atan2(a).to_degrees().rem_euclid(360.)
+            }
+        };
+        let (l1, a1, b1) = (self.l, self.a, self.b);
+        let (l2, a2, b2) = (other.l, other.a, other.b);
+        let c_mean = (a1.hypot(b1) + a2.hypot(b2)) / 2.;
+        let g = 0.5 * (1. - (c_mean.powi(7) / (c_mean.powi(7) + POW_25_7)).sqrt());
+        let (a1, a2) = ((1. + g) * a1, (1. + g) * a2);
+        let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+        let (h1, h2) = (hue(b1, a1), hue(b2, a2));
+
+        let delta_l = l2 - l1;
+        let delta_c = c2 - c1;
+        let delta_h = if c1 * c2 == 0. {
+            0.
+        } else if h2 - h1 > 180. {
+            h2 - h1 - 360.
+        } else if h2 - h1 < -180. {
+            h2 - h1 + 360.
+        } else {
+            h2 - h1
+        };
+        let delta_h = 2. * (c1 * c2).sqrt() * (delta_h / 2.).to_radians().sin();
+
+        let l_mean = (l1 + l2) / 2.;
+        let c_mean = (c1 + c2) / 2.;
+        let h_mean = if c1 * c2 == 0. {
+            h1 + h2
+        } else if (h1 - h2).abs() <= 180. {
+            (h1 + h2) / 2.
+        } else if h1 + h2 < 360. {
+            (h1 + h2 + 360.) / 2.
+        } else {
+            (h1 + h2 - 360.) / 2.
+        };
+        let cos = |degrees: f32| degrees.to_radians().cos();
+        let t =
+            1. - 0.17 * cos(h_mean - 30.) + 0.24 * cos(2. * h_mean) + 0.32 * cos(3. * h_mean + 6.)
+                - 0.20 * cos(4. * h_mean - 63.);
+        let delta_theta = 30. * (-((h_mean - 275.) / 25.).powi(2)).exp();
+        let r_c = 2. * (c_mean.powi(7) / (c_mean.powi(7) + POW_25_7)).sqrt();
+        let s_l = 1. + 0.015 * (l_mean - 50.).powi(2) / (20. + (l_mean - 50.).powi(2)).sqrt();
+        let s_c = 1. + 0.045 * c_mean;
+        let s_h = 1. + 0.015 * c_mean * t;
+        let r_t = -(2. * delta_theta).to_radians().sin() * r_c;
+
+        let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+        (l * l + c * c + h * h + r_t * c * h).max(0.).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lab;
+
+    /// Test data of Sharma, Wu and Dalal (2005), Table 1: both colors and the expected ΔE00
+    const SHARMA: [([f32; 3], [f32; 3], f32); 34] = [
+        (
+            [50.0000, 2.6772, -79.7751],
+            [50.0000, 0.0000, -82.7485],
+            2.0425,
+        ),
+        (
+            [50.0000, 3.1571, -77.2803],
+            [50.0000, 0.0000, -82.7485],
+            2.8615,
+        ),
+        (
+            [50.0000, 2.8361, -74.0200],
+            [50.0000, 0.0000, -82.7485],
+            3.4412,
+        ),
+        (
+            [50.0000, -1.3802, -84.2814],
+            [50.0000, 0.0000, -82.7485],
+            1.0000,
+        ),
+        (
+            [50.0000, -1.1848, -84.8006],
+            [50.0000, 0.0000, -82.7485],
+            1.0000,
+        ),
+        (
+            [50.0000, -0.9009, -85.5211],
+            [50.0000, 0.0000, -82.7485],
+            1.0000,
+        ),
+        (
+            [50.0000, 0.0000, 0.0000],
+            [50.0000, -1.0000, 2.0000],
+            2.3669,
+        ),
+        (
+            [50.0000, -1.0000, 2.0000],
+            [50.0000, 0.0000, 0.0000],
+            2.3669,
+        ),
+        (
+            [50.0000, 2.4900, -0.0010],
+            [50.0000, -2.4900, 0.0009],
+            7.1792,
+        ),
+        (
+            [50.0000, 2.4900, -0.0010],
+            [50.0000, -2.4900, 0.0010],
+            7.1792,
+        ),
+        (
+            [50.0000, 2.4900, -0.0010],
+            [50.0000, -2.4900, 0.0011],
+            7.2195,
+        ),
+        (
+            [50.0000, 2.4900, -0.0010],
+            [50.0000, -2.4900, 0.0012],
+            7.2195,
+        ),
+        (
+            [50.0000, -0.0010, 2.4900],
+            [50.0000, 0.0009, -2.4900],
+            4.8045,
+        ),
+        (
+            [50.0000, -0.0010, 2.4900],
+            [50.0000, 0.0010, -2.4900],
+            4.8045,
+        ),
+        (
+            [50.0000, -0.0010, 2.4900],
+            [50.0000, 0.0011, -2.4900],
+            4.7461,
+        ),
+        (
+            [50.0000, 2.5000, 0.0000],
+            [50.0000, 0.0000, -2.5000],
+            4.3065,
+        ),
+        (
+            [50.0000, 2.5000, 0.0000],
+            [73.0000, 25.0000, -18.0000],
+            27.1492,
+        ),
+        (
+            [50.0000, 2.5000, 0.0000],
+            [61.0000, -5.0000, 29.0000],
+            22.8977,
+        ),
+        (
+            [50.0000, 2.5000, 0.0000],
+            [56.0000, -27.0000, -3.0000],
+            31.9030,
+        ),
+        (
+            [50.0000, 2.5000, 0.0000],
+            [58.0000, 24.0000, 15.0000],
+            19.4535,
+        ),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.1736, 0.5854], 1.0000),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.2972, 0.0000], 1.0000),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 1.8634, 0.5757], 1.0000),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.2592, 0.3350], 1.0000),
+        (
+            [60.2574, -34.0099, 36.2677],
+            [60.4626, -34.1751, 39.4387],
+            1.2644,
+        ),
+        (
+            [63.0109, -31.0961, -5.8663],
+            [62.8187, -29.7946, -4.0864],
+            1.2630,
+        ),
+        (
+            [61.2901, 3.7196, -5.3901],
+            [61.4292, 2.2480, -4.9620],
+            1.8731,
+        ),
+        (
+            [35.0831, -44.1164, 3.7933],
+            [35.0232, -40.0716, 1.5901],
+            1.8645,
+        ),
+        (
+            [22.7233, 20.0904, -46.6940],
+            [23.0331, 14.9730, -42.5619],
+            2.0373,
+        ),
+        (
+            [36.4612, 47.8580, 18.3852],
+            [36.2715, 50.5065, 21.2231],
+            1.4146,
+        ),
+        (
+            [90.8027, -2.0831, 1.4410],
+            [91.1528, -1.6435, 0.0447],
+            1.4441,
+        ),
+        (
+            [90.9257, -0.5406, -0.9208],
+            [88.6381, -0.8985, -0.7239],
+            1.5381,
+        ),
+        (
+            [6.7747, -0.2908, -2.4247],
+            [5.8714, -0.0985, -2.2286],
+            0.6377,
+        ),
+        (
+            [2.0776, 0.0795, -1.1350],
+            [0.9033, -0.0636, -0.5514],
+            0.9082,
+        ),
+    ];
+
+    fn lab([l, a, b]: [f32; 3]) -> Lab {
+        Lab { l, a, b }
+    }
+
+    #[test]
+    fn delta_e_2000_matches_sharma() {
+        for (index, (first, second, expected)) in SHARMA.into_iter().enumerate() {
+            let delta_e = lab(first).delta_e_2000(&lab(second));
+            assert!(
+                (delta_e - expected).abs() < 1e-4,
+                "pair {}: {delta_e} instead of {expected}",
+                index + 1
+            );
+        }
+    }
+
+    #[test]
+    fn delta_e_2000_is_symmetric() {
+        for (first, second, _) in SHARMA {
+            let forward = lab(first).delta_e_2000(&lab(second));
+            let backward = lab(second).delta_e_2000(&lab(first));
+            assert!((forward - backward).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn from_rgb_white_and_black() {
+        let white = Lab::from_rgb([255, 255, 255]);
+        assert!((white.l - 100.).abs() < 0.01);
+        assert!(white.a.abs() < 0.01 && white.b.abs() < 0.01);
+        let black = Lab::from_rgb([0, 0, 0]);
+        assert!(black.l.abs() < 0.01);
+    }
+}
@@ -1,46 +1,572 @@
 pub const GOLD_AS_RGB: [u8; 3] = [218, 165, 32];
-const HIT: image::Luma<u8> = image::Luma([255]);
-const MISSED: image::Luma<u8> = image::Luma([0]);
+pub const HIT: image::Luma<u8> = image::Luma([255]);
+pub const MISSED: image::Luma<u8> = image::Luma([0]);
 
+mod axis;
+mod calibration;
+mod color_metric;
+mod pipeline;
+mod plot_area;
+mod polar;
+mod resample;
 mod step0_crop;
+mod step0_gridlines;
+mod step1_clustering;
 mod step1_color_extraction;
+mod step1_legend;
 mod step2_color_filtering;
+mod step2_shape_filtering;
+mod step3_bars;
+mod step3_error_bars;
 mod step3_group;
+mod step3_scatter;
+mod step4_dashes;
 mod step4_stitch;
+mod step5_confidence;
+mod step5_line_style;
+mod step5_polyline;
+mod step6_postprocessing;
 mod unit_geometry;
 
 use std::path::Path;
 
+pub use axis::{AxesMapping, Axis, AxisFormat, AxisScale, TimeUnit};
+pub use calibration::{AxisCalibration, Calibration, CalibrationFit, ReferencePoint};
+pub use color_metric::ColorMetric;
 use itertools::Itertools;
+pub use pipeline::{
+    AggregationStage, BarStage, ConfidenceStage, CropStage, DefaultAggregation, DefaultBars,
+    DefaultConfidence, DefaultCrop, DefaultErrorBars, DefaultGridlines, DefaultGrouping,
+    DefaultLegend, DefaultMapping, DefaultMarkers, DefaultMask, DefaultPalette,
+    DefaultPostprocessing, DefaultShapeFilter, DefaultStitching, ErrorBarStage, GridlineStage,
+    GroupingStage, LegendStage, Mapping, MappingStage, MarkerStage, MaskStage, PaletteStage,
+    Pipeline, PostprocessingStage, ShapeFilterStage, StitchingStage,
+};
+pub use plot_area::detect_plot_area;
+pub use polar::{AngleDirection, PolarCalibration};
+pub use step0_crop::{Crop, CropMode, ImageInterpolate};
+pub use step1_color_extraction::extract_colors;
+pub use step2_color_filtering::{color_filtering, color_weights};
+pub use step3_bars::BarPixels;
+pub use step3_error_bars::ErrorBarPixels;
+pub use step3_group::{
+    group_large_components_and_remaining, Centroid, CombinedVerticals, GraphMultiNode, MultiNode,
+    VerticalComponent, VerticalComponentCombined, X,
+};
+pub use step3_scatter::Blob;
+pub use step4_dashes::bridge;
+pub use step4_stitch::stitch;
+pub use step5_line_style::line_style;
+pub use step5_polyline::connect_touching;
 pub use unit_geometry::{UnitInterval, UnitPoint, UnitQuadrilateral};
 
-pub type ImageRgba = (image::Rgba<u8>, Vec<(f32, f32)>);
+pub type ImageRgba = (image::Rgba<u8>, Vec<(f64, f64)>);
+/// Intermediate image of the detection of one color
+pub type ColorImage = (
+    image::Rgba<u8>,
+    image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+);
+pub type ColorMask = (
+    image::Rgba<u8>,
+    image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum DetectionMode {
+    /// Continuous curves
+    #[default]
+    Lines,
+    /// Isolated markers, e.g. circles, squares, triangles or crosses
+    Scatter,
+    /// Filled rectangles, grouped bars are distinguished by color
+    Bars,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum BarOrientation {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum CurveOutput {
+    /// One value per x, the center of all vertical runs of a column
+    #[default]
+    Function,
+    /// Every vertical run as its own sample, ordered along the curve (x(t), y(t)),
+    /// e.g. for hysteresis loops, ellipses or S-shaped curves
+    Polyline,
+}
+impl CurveOutput {
+    /// Sub-pixel positions of the samples of a graph
+    fn samples(&self, graph: &step3_group::GraphMultiNode) -> Vec<(f32, f32)> {
+        match self {
+            CurveOutput::Function => graph
+                .ys
+                .iter()
+                .enumerate()
+                .filter_map(|(x, ys)| ys.centroid().map(|y| (x as f32, y)))
+                .collect(),
+            CurveOutput::Polyline => step5_polyline::polyline(graph),
+        }
+    }
+}
+
+/// Smoothing of the extracted curves, along the points of a curve
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Smoothing {
+    #[default]
+    None,
+    MovingAverage,
+    /// Quadratic least squares fit within the window, keeps peaks better than the moving average
+    SavitzkyGolay,
+}
+
+/// X values onto which all plots are resampled for the csv
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub enum ResampleGrid {
+    /// Evenly spaced points from the smallest to the largest x of all plots
+    Count(u32),
+    /// Points from the smallest x of all plots up to the largest one
+    Step(f64),
+    Explicit(Vec<f64>),
+}
+impl Default for ResampleGrid {
+    fn default() -> Self {
+        ResampleGrid::Count(100)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Natural cubic spline through all points
+    Spline,
+}
+
+/// Value of a resampled plot at x beyond its first or last point
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutsideRange {
+    #[default]
+    Nan,
+    /// The value of the nearest end
+    Nearest,
+    /// Continued linearly with the slope at the end
+    Extrapolate,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Resampling {
+    pub grid: ResampleGrid,
+    pub interpolation: Interpolation,
+    pub outside: OutsideRange,
+}
+
+/// Pattern of a curve along x, distinguishes curves of the same color
+#[derive(
+    serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+pub enum LineStyle {
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+/// How the curve colors are found
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum PaletteMethod {
+    /// Each pixel belongs to the first color within the color radius
+    #[default]
+    FirstSeen,
+    /// Median cut and k-means on the color histogram, colors are the cluster centroids
+    Clustering,
+}
+
+/// `image::Rgba` as `[r, g, b, a]`, the image crate has no serde support for pixels
+mod rgba_serde {
+    use serde::{Deserialize, Serialize};
+
+    pub fn serialize<S: serde::Serializer>(
+        color: &image::Rgba<u8>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        color.0.serialize(serializer)
+    }
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<image::Rgba<u8>, D::Error> {
+        <[u8; 4]>::deserialize(deserializer).map(image::Rgba)
+    }
+
+    /// Results per color
+    pub mod per_color {
+        use serde::{Deserialize, Serialize};
+
+        pub fn serialize<S: serde::Serializer, T: Serialize>(
+            results: &[(image::Rgba<u8>, T)],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(results.iter().map(|(color, result)| (color.0, result)))
+        }
+        pub fn deserialize<'de, D: serde::Deserializer<'de>, T: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<(image::Rgba<u8>, T)>, D::Error> {
+            Vec::<([u8; 4], T)>::deserialize(deserializer).map(|results| {
+                results
+                    .into_iter()
+                    .map(|(color, result)| (image::Rgba(color), result))
+                    .collect()
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PaletteColor {
+    #[serde(with = "rgba_serde")]
+    pub color: image::Rgba<u8>,
+    pub pixel_count: u32,
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[serde(default)]
 pub struct Settings {
+    pub mode: DetectionMode,
+    pub step0_crop_mode: CropMode,
+    pub step0_remove_gridlines: bool,
+    pub step0_gridline_min_length_fraction: f32,
     pub step1_width_minimial_fraction: f32,
     pub step1_height_maximal_fraction: f32,
     pub step1_ignore_gray: bool,
+    pub step1_palette: PaletteMethod,
+    pub step1_background: Option<[u8; 3]>,
+    pub step1_monochrome: bool,
+    pub step1_detect_legend: bool,
     pub step1_close_count: u8,
     pub step1_step2_color_radius: u8,
+    pub step1_step2_color_metric: ColorMetric,
+    pub step2_frame_min_length_fraction: f32,
+    pub step2_max_symbol_size: u32,
     pub step3_min_width_fraction: f32,
+    pub step3_marker_min_pixels: u32,
+    pub step3_marker_max_size_fraction: f32,
+    pub step3_marker_min_count: u32,
+    pub step3_bar_orientation: BarOrientation,
+    pub step3_bar_min_width: u32,
+    pub step3_detect_error_bars: bool,
+    pub step3_error_bar_max_width: u32,
+    pub step3_error_bar_min_length: u32,
     pub step4_component_jump_height_fraction: f32,
+    pub step4_bridge_dashes: bool,
+    pub step4_max_dash_gap: u32,
+    pub step5_curve_output: CurveOutput,
     pub step6_fit_graph_color: Option<[u8; 3]>,
+    pub step6_despike_window: u32,
+    pub step6_smoothing: Smoothing,
+    pub step6_smoothing_window: u32,
+    pub step6_simplify_tolerance: f64,
+    pub step6_resample: bool,
+    pub step6_resample_grid: ResampleGrid,
+    pub step6_resample_interpolation: Interpolation,
+    pub step6_resample_outside: OutsideRange,
 }
 impl Default for Settings {
     fn default() -> Self {
         Self {
+            mode: DetectionMode::Lines,
+            step0_crop_mode: CropMode::Bilinear,
+            step0_remove_gridlines: false,
+            step0_gridline_min_length_fraction: 0.5,
             step1_step2_color_radius: 5,
+            step1_step2_color_metric: ColorMetric::Rgb,
             step1_width_minimial_fraction: 0.3,
             step1_height_maximal_fraction: 0.1,
             step1_close_count: 0,
             step1_ignore_gray: true,
+            step1_palette: PaletteMethod::FirstSeen,
+            step1_background: None,
+            step1_monochrome: false,
+            step1_detect_legend: false,
+            step2_frame_min_length_fraction: 0.5,
+            step2_max_symbol_size: 12,
             step3_min_width_fraction: 0.05,
+            step3_marker_min_pixels: 4,
+            step3_marker_max_size_fraction: 0.05,
+            step3_marker_min_count: 3,
+            step3_bar_orientation: BarOrientation::Vertical,
+            step3_bar_min_width: 3,
+            step3_detect_error_bars: false,
+            step3_error_bar_max_width: 3,
+            step3_error_bar_min_length: 8,
             step4_component_jump_height_fraction: 0.02,
+            step4_bridge_dashes: false,
+            step4_max_dash_gap: 15,
+            step5_curve_output: CurveOutput::Function,
             step6_fit_graph_color: Some(GOLD_AS_RGB),
+            step6_despike_window: 0,
+            step6_smoothing: Smoothing::None,
+            step6_smoothing_window: 5,
+            step6_simplify_tolerance: 0.,
+            step6_resample: false,
+            step6_resample_grid: ResampleGrid::default(),
+            step6_resample_interpolation: Interpolation::Linear,
+            step6_resample_outside: OutsideRange::Nan,
+        }
+    }
+}
+
+impl Settings {
+    /// Detected before step 1 if not set
+    fn background(&self) -> image::Rgba<u8> {
+        let [r, g, b] = self.step1_background.unwrap_or([255, 255, 255]);
+        image::Rgba([r, g, b, 255])
+    }
+    /// Markers and bars are sparse, hence the minimal width is only applied to lines
+    fn width_minimal_fraction(&self) -> f32 {
+        match self.mode {
+            DetectionMode::Lines => self.step1_width_minimial_fraction,
+            DetectionMode::Scatter | DetectionMode::Bars => 0.,
+        }
+    }
+    fn resampling(&self) -> Option<Resampling> {
+        self.step6_resample.then(|| Resampling {
+            grid: self.step6_resample_grid.clone(),
+            interpolation: self.step6_resample_interpolation,
+            outside: self.step6_resample_outside,
+        })
+    }
+    /// Gaps between dashes do not count against the minimal width if dashes are bridged
+    fn max_dash_gap(&self) -> usize {
+        if self.step4_bridge_dashes {
+            self.step4_max_dash_gap as usize
+        } else {
+            0
         }
     }
+    fn detects_error_bars(&self) -> bool {
+        self.step3_detect_error_bars && self.mode != DetectionMode::Bars
+    }
+    /// Error bars and the frame of monochrome plots are removed after color extraction,
+    /// hence they must not exclude a color
+    fn color_height_maximal_fraction(&self) -> f32 {
+        if self.detects_error_bars() || self.step1_monochrome {
+            1.
+        } else {
+            self.height_maximal_fraction()
+        }
+    }
+    /// Bars fill whole columns, hence the maximal height is not applied to them
+    fn height_maximal_fraction(&self) -> f32 {
+        match self.mode {
+            DetectionMode::Lines | DetectionMode::Scatter => self.step1_height_maximal_fraction,
+            DetectionMode::Bars => 1.,
+        }
+    }
+}
+
+/// Dark blue pixels of the color, as background for drawing the detected parts in step 3 to 5
+fn mask_preview(
+    color_filtered: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    imageproc::map::map_colors(color_filtered, |c| {
+        if c == HIT {
+            image::Rgba([0, 0, 128, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+/// Stages may be replaced, hence their output is not trusted to lie within the image
+fn draw_pixel<P: image::Pixel>(
+    image: &mut image::ImageBuffer<P, Vec<P::Subpixel>>,
+    x: u32,
+    y: u32,
+    color: P,
+) {
+    if let Some(pixel) = image.get_pixel_mut_checked(x, y) {
+        *pixel = color;
+    }
+}
+
+fn component_color(index: usize) -> image::Rgba<u8> {
+    const H: u8 = 255;
+    const N: u8 = 0;
+    match index % 7 {
+        0 => image::Rgba([H, H, H, H]),
+        1 => image::Rgba([H, H, N, H]),
+        2 => image::Rgba([N, H, H, H]),
+        3 => image::Rgba([H, N, H, H]),
+        4 => image::Rgba([H, N, N, H]),
+        5 => image::Rgba([N, H, N, H]),
+        6 => image::Rgba([N, N, H, H]),
+        _ => unreachable!(),
+    }
+}
+
+/// Error bars of step 3 in the colors of [`component_color`]
+fn error_bar_preview(
+    color_filtered: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    error_bars: &[step3_error_bars::ErrorBarPixels],
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut image = mask_preview(color_filtered);
+    for (index, error_bar) in error_bars.iter().enumerate() {
+        for &(x, y) in &error_bar.pixels {
+            draw_pixel(&mut image, x, y, component_color(index));
+        }
+    }
+    image
+}
+
+fn bar_preview(
+    color_filtered: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    bars: &[step3_bars::BarPixels],
+    orientation: BarOrientation,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut image = mask_preview(color_filtered);
+    for (index, bar) in bars.iter().enumerate() {
+        for line in bar.start..=bar.end {
+            for position in bar.low.round() as u32..=bar.high.round() as u32 {
+                let (x, y) = match orientation {
+                    BarOrientation::Vertical => (line, position),
+                    BarOrientation::Horizontal => (position, line),
+                };
+                draw_pixel(&mut image, x, y, component_color(index));
+            }
+        }
+    }
+    image
+}
+
+fn marker_preview(
+    color_filtered: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    blobs: &[step3_scatter::Blob],
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut image = mask_preview(color_filtered);
+    for (index, blob) in blobs.iter().enumerate() {
+        for &(x, y) in &blob.pixels {
+            draw_pixel(&mut image, x, y, component_color(index));
+        }
+    }
+    image
+}
+
+/// Graphs of steps 3 to 5, the remaining verticals are gray
+fn graph_preview(
+    color_filtered: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    graphs: &[step3_group::GraphMultiNode],
+    remaining: &[step3_group::CombinedVerticals],
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut image = mask_preview(color_filtered);
+    for (index, graph) in graphs.iter().enumerate() {
+        for (x, y) in graph.ys.iter().enumerate() {
+            if let Some(y) = y.mean() {
+                draw_pixel(&mut image, x as _, y, component_color(index));
+            }
+        }
+    }
+    for step3_group::CombinedVerticals { x_start, combined } in remaining {
+        for (x_offset, ys) in combined.iter().enumerate() {
+            let x = x_start.0 + x_offset as u32;
+            draw_pixel(&mut image, x, ys.mean(), image::Rgba([128, 128, 128, 255]));
+        }
+    }
+    image
+}
+
+/// The detected plots drawn onto the crop, none if nothing was detected
+fn plots_image(
+    line_detected: &LineDetected,
+    settings: &Settings,
+) -> Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
+    let cropped = line_detected.cropped.as_ref()?;
+    if line_detected.graphs.is_empty()
+        && line_detected.markers.is_empty()
+        && line_detected.bars.is_empty()
+    {
+        return None;
+    }
+    let mut image_with_plots = cropped.clone();
+    let fit_color = |color: &image::Rgba<u8>| {
+        if let Some([r, g, b]) = settings.step6_fit_graph_color {
+            image::Rgba([r, g, b, 255])
+        } else {
+            *color
+        }
+    };
+    for (color, graphs) in &line_detected.graphs {
+        let color = fit_color(color);
+        for graph in graphs {
+            for (x, y) in settings.step5_curve_output.samples(graph) {
+                draw_pixel(&mut image_with_plots, x as u32, y.round() as u32, color);
+            }
+        }
+    }
+    for (color, markers) in &line_detected.markers {
+        let color = fit_color(color);
+        for marker in markers {
+            let x = marker.pixel_x.round() as i32;
+            let y = marker.pixel_y.round() as i32;
+            for (dx, dy) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (x, y) = (x + dx, y + dy);
+                if x >= 0 && y >= 0 {
+                    draw_pixel(&mut image_with_plots, x as _, y as _, color);
+                }
+            }
+        }
+    }
+    for (color, bars) in &line_detected.bars {
+        let color = fit_color(color);
+        for bar in bars {
+            let [left, top, right, bottom] = bar.pixel_rect.map(|x| x.round() as i32);
+            let rect = imageproc::rect::Rect::at(left, top)
+                .of_size((right - left).max(1) as u32, (bottom - top).max(1) as u32);
+            imageproc::drawing::draw_hollow_rect_mut(&mut image_with_plots, rect, color);
+        }
+    }
+    for (color, error_bars) in &line_detected.error_bar_pixels {
+        let color = fit_color(color);
+        for error_bar in error_bars {
+            let x = error_bar.center_x();
+            let (top, bottom) = (error_bar.y_min as f32, error_bar.y_max as f32);
+            imageproc::drawing::draw_line_segment_mut(
+                &mut image_with_plots,
+                (x, top),
+                (x, bottom),
+                color,
+            );
+            if error_bar.has_caps {
+                for y in [top, bottom] {
+                    imageproc::drawing::draw_line_segment_mut(
+                        &mut image_with_plots,
+                        (x - 2., y),
+                        (x + 2., y),
+                        color,
+                    );
+                }
+            }
+        }
+    }
+    Some(image_with_plots)
+}
+
+/// The samples of the graphs colored by [`step5_confidence::color`]
+fn confidence_image<'a>(
+    cropped: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    samples: impl Iterator<Item = (&'a (f32, f32), &'a f32)>,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut image_with_confidence = cropped.clone();
+    for ((x, y), confidence) in samples {
+        draw_pixel(
+            &mut image_with_confidence,
+            *x as u32,
+            y.round() as u32,
+            step5_confidence::color(*confidence),
+        );
+    }
+    image_with_confidence
 }
 
 fn color_distance(cc: &image::Rgba<u8>, c: &image::Rgba<u8>) -> u8 {
@@ -60,120 +586,896 @@ fn color_distance_three(cc: &image::Rgb<u8>, c: &image::Rgba<u8>) -> u8 {
 pub enum Error {
     StepSettingsInvalid { steps_x: u32, steps_y: u32 },
     CroppedImageToSmall { width: u32, height: u32 },
+    AxisLimitsInvalid { min: f64, max: f64 },
+    AxisLimitsNotPositive { min: f64, max: f64 },
+    AxisLogBaseInvalid { base: f64 },
+    CalibrationInsufficientPoints { count: usize },
+    CalibrationValueNotPositive { value: f64 },
+    CalibrationDegenerate,
+    QuadrilateralDegenerate,
+    QuadrilateralSelfIntersecting,
+    QuadrilateralNotConvex,
+    PolarAngleInvalid { angle: f32 },
+    PolarRadiusTooSmall { radius: f32 },
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::StepSettingsInvalid { steps_x, steps_y } => write!(
+                f,
+                "Cropped image of {steps_x}x{steps_y} pixels is too small, a crop requires at least \
+                2x2 and the detection 100x100"
+            ),
+            Error::CroppedImageToSmall { width, height } => write!(
+                f,
+                "Cropped image of {width}x{height} pixels is too small, at least 100 pixels in one direction are required"
+            ),
+            Error::AxisLimitsInvalid { min, max } => {
+                write!(f, "Axis limits {min} and {max} must be finite and different")
+            }
+            Error::AxisLimitsNotPositive { min, max } => write!(
+                f,
+                "Axis limits {min} and {max} must be positive for a logarithmic scale"
+            ),
+            Error::AxisLogBaseInvalid { base } => write!(
+                f,
+                "Logarithm base {base} must be positive and different from 1"
+            ),
+            Error::CalibrationInsufficientPoints { count } => write!(
+                f,
+                "Calibration needs at least 2 reference points per axis, got {count}"
+            ),
+            Error::CalibrationValueNotPositive { value } => write!(
+                f,
+                "Reference value {value} must be positive for a logarithmic scale"
+            ),
+            Error::CalibrationDegenerate => write!(
+                f,
+                "Reference points do not span the axes, e.g. equal values or parallel axes"
+            ),
+            Error::QuadrilateralDegenerate => write!(f, "Crop area has collapsed corners or edges"),
+            Error::QuadrilateralSelfIntersecting => write!(f, "Crop area has crossing edges"),
+            Error::QuadrilateralNotConvex => {
+                write!(f, "Crop area must be convex for perspective rectification")
+            }
+            Error::PolarAngleInvalid { angle } => write!(f, "Zero angle {angle} is not finite"),
+            Error::PolarRadiusTooSmall { radius } => write!(
+                f,
+                "Reference circle radius of {radius} pixels is too small"
+            ),
+        }
+    }
+}
+impl std::error::Error for Error {}
+/// Overall quality of a curve
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct CurveQuality {
+    /// Fraction of the columns between the first and the last point which are backed by pixels
+    pub coverage: f32,
+    /// Mean confidence of the points
+    pub confidence: f32,
+}
+impl CurveQuality {
+    pub fn score(&self) -> f32 {
+        self.coverage * self.confidence
+    }
+}
+
+/// Legend box within the cropped image, it is excluded from the detection of the curves
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Legend {
+    /// Left, top, right and bottom pixel, included
+    pub rect: [u32; 4],
+    pub entries: Vec<LegendEntry>,
+}
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct LegendEntry {
+    #[serde(with = "rgba_serde")]
+    pub color: image::Rgba<u8>,
+    pub swatch: [u32; 4],
+    /// Image of the label text, there is no text recognition, not serialized
+    #[serde(skip)]
+    pub label: image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Marker {
+    /// Data values
+    pub x: f64,
+    pub y: f64,
+    /// Sub-pixel centroid within the cropped image
+    pub pixel_x: f32,
+    pub pixel_y: f32,
+    pub pixel_count: u32,
+}
+
+/// Vertical bars: position/width along x and value along y, horizontal bars vice versa
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Bar {
+    pub orientation: BarOrientation,
+    pub center: f64,
+    pub width: f64,
+    /// Data value at the end of the bar away from the baseline, e.g. the top of positive and the
+    /// bottom of negative vertical bars
+    pub value: f64,
+    /// Data value at the start of the bar, usually the baseline
+    pub base: f64,
+    /// Left, top, right and bottom edge within the cropped image
+    pub pixel_rect: [f32; 4],
+    /// Center of the end of the bar within the cropped image, where `value` is taken
+    pub pixel_end: (f32, f32),
+}
+
+impl Bar {
+    /// Without a baseline the bars grow up or right
+    fn new(
+        bar: &step3_bars::BarPixels,
+        orientation: BarOrientation,
+        baseline: Option<f32>,
+        mapping: &dyn Mapping,
+    ) -> Self {
+        let center_pixel = (bar.start + bar.end) as f32 / 2.;
+        let left = bar.start as f32 - 0.5;
+        let right = bar.end as f32 + 0.5;
+        let low = bar.low - 0.5;
+        let high = bar.high + 0.5;
+        let base_is_high = match baseline {
+            Some(baseline) => (baseline - high).abs() < (baseline - low).abs(),
+            None => orientation == BarOrientation::Vertical,
+        };
+        let (start, end) = if base_is_high {
+            (high, low)
+        } else {
+            (low, high)
+        };
+        match orientation {
+            BarOrientation::Vertical => {
+                let (center, value) = mapping.map(center_pixel, end);
+                let base = mapping.map(center_pixel, start).1;
+                let width = (mapping.map(right, end).0 - mapping.map(left, end).0).abs();
+                let pixel_rect = [left, low, right, high];
+                Bar {
+                    orientation,
+                    center,
+                    width,
+                    value,
+                    base,
+                    pixel_rect,
+                    pixel_end: (center_pixel, end),
+                }
+            }
+            BarOrientation::Horizontal => {
+                let (value, center) = mapping.map(end, center_pixel);
+                let base = mapping.map(start, center_pixel).0;
+                let width = (mapping.map(end, right).1 - mapping.map(end, left).1).abs();
+                let pixel_rect = [low, left, high, right];
+                Bar {
+                    orientation,
+                    center,
+                    width,
+                    value,
+                    base,
+                    pixel_rect,
+                    pixel_end: (end, center_pixel),
+                }
+            }
+        }
+    }
+}
+
+/// Vertical error bar attached to a point of a curve or to a marker
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct ErrorBar {
+    /// Index of the point within its plot
+    pub index: usize,
+    /// Data values of the point
+    pub x: f64,
+    pub y: f64,
+    /// Data values at the ends of the bar
+    pub lower: f64,
+    pub upper: f64,
+    pub has_caps: bool,
+    /// Column and ends of the bar within the cropped image
+    pub pixel_x: f32,
+    pub pixel_top: f32,
+    pub pixel_bottom: f32,
+}
+impl ErrorBar {
+    fn new(
+        bar: &step3_error_bars::ErrorBarPixels,
+        index: usize,
+        (pixel_x, pixel_y): (f32, f32),
+        mapping: &dyn Mapping,
+    ) -> Self {
+        let (x, y) = mapping.map(pixel_x, pixel_y);
+        let pixel_top = bar.y_min as f32;
+        let pixel_bottom = bar.y_max as f32;
+        let top = mapping.map(bar.center_x(), pixel_top).1;
+        let bottom = mapping.map(bar.center_x(), pixel_bottom).1;
+        Self {
+            index,
+            x,
+            y,
+            lower: top.min(bottom),
+            upper: top.max(bottom),
+            has_caps: bar.has_caps,
+            pixel_x: bar.center_x(),
+            pixel_top,
+            pixel_bottom,
+        }
+    }
+}
+
+/// Step which detected a curve
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum CurveSource {
+    /// Traced lines, steps 3 to 5
+    Graph,
+    /// Markers of scatter plots, step 3
+    Markers,
+    /// Bars, step 3
+    Bars,
+}
+
+pub enum DebugImageBuffer<'a> {
+    Rgba(&'a image::ImageBuffer<image::Rgba<u8>, Vec<u8>>),
+    /// Pixels of a color are [`HIT`], all others [`MISSED`]
+    Mask(&'a image::ImageBuffer<image::Luma<u8>, Vec<u8>>),
 }
-#[derive(Default)]
+impl DebugImageBuffer<'_> {
+    pub fn to_rgba(&self) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+        match self {
+            DebugImageBuffer::Rgba(image) => (*image).clone(),
+            DebugImageBuffer::Mask(mask) => {
+                imageproc::map::map_colors(*mask, |image::Luma([l])| image::Rgba([l, l, l, 255]))
+            }
+        }
+    }
+
+    fn save(&self, path: impl AsRef<Path>) -> image::ImageResult<()> {
+        match self {
+            DebugImageBuffer::Rgba(image) => image.save(path),
+            DebugImageBuffer::Mask(mask) => mask.save(path),
+        }
+    }
+}
+
+/// Intermediate image of a step of the detection
+pub struct DebugImage<'a> {
+    /// e.g. `step2`, or `final` for the images with the detected plots
+    pub step: &'static str,
+    /// e.g. `color_filtered`
+    pub name: &'static str,
+    /// Color of the curves, None if the image covers all colors
+    pub color: Option<image::Rgba<u8>>,
+    /// Position among the images with the same step and name
+    pub index: Option<usize>,
+    pub image: DebugImageBuffer<'a>,
+}
+impl DebugImage<'_> {
+    /// Name of the file written by [`LineDetected::save`], e.g. `step2_0_color_filtered.png`
+    pub fn file_name(&self) -> String {
+        let Self {
+            step, name, index, ..
+        } = self;
+        match index {
+            Some(index) => format!("{step}_{index}_{name}.png"),
+            None => format!("{step}_{name}.png"),
+        }
+    }
+}
+
+/// One detected plot, the per-point vectors have the same length as `points`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Curve {
+    pub name: String,
+    pub color: [u8; 3],
+    pub source: CurveSource,
+    /// Data values, after the post-processing of step 6
+    pub points: Vec<(f64, f64)>,
+    /// Sub-pixel positions within the cropped image, after the filters of step 6
+    pub pixel_points: Vec<(f32, f32)>,
+    /// Bridged gaps between dashes instead of detected pixels
+    pub interpolated: Vec<bool>,
+    pub confidence: Vec<f32>,
+    /// None for markers and bars
+    pub line_style: Option<LineStyle>,
+    /// None for markers and bars
+    pub quality: Option<CurveQuality>,
+    pub error_bars: Vec<ErrorBar>,
+    /// Index of the entry of [`LineDetected::legend`] with the color of the curve
+    pub legend_entry: Option<usize>,
+}
+
+/// The serialized results leave out the images and the intermediate data of the steps
+#[derive(Default, serde::Serialize, serde::Deserialize)]
 pub struct LineDetected {
+    #[serde(skip)]
     cropped: Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
-    colors: Option<Vec<image::Rgba<u8>>>,
-    color_filtered: Vec<image::ImageBuffer<image::Luma<u8>, Vec<u8>>>,
-    grouped_image: Vec<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
-    stitched_image: Vec<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
-    aggregated_image: Vec<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
+    #[serde(skip)]
+    gridlines_removed: Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
+    legend: Option<Legend>,
+    #[serde(skip)]
+    legend_removed: Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
+    colors: Option<Vec<PaletteColor>>,
+    background: [u8; 3],
+    #[serde(skip)]
+    color_filtered: Vec<ColorMask>,
+    #[serde(skip)]
+    shape_filtered_image: Vec<ColorMask>,
+    #[serde(skip)]
+    grouped_image: Vec<ColorImage>,
+    #[serde(skip)]
+    stitched_image: Vec<ColorImage>,
+    #[serde(skip)]
+    aggregated_image: Vec<ColorImage>,
+    #[serde(skip)]
+    marker_image: Vec<ColorImage>,
+    #[serde(with = "rgba_serde::per_color")]
+    markers: Vec<(image::Rgba<u8>, Vec<Marker>)>,
+    #[serde(skip)]
+    bar_image: Vec<ColorImage>,
+    #[serde(with = "rgba_serde::per_color")]
+    bars: Vec<(image::Rgba<u8>, Vec<Bar>)>,
+    #[serde(skip)]
+    error_bar_image: Vec<ColorImage>,
+    #[serde(skip)]
+    error_bar_pixels: Vec<(image::Rgba<u8>, Vec<step3_error_bars::ErrorBarPixels>)>,
+    #[serde(skip)]
     remaining_vertices: Vec<Vec<step3_group::CombinedVerticals>>,
+    #[serde(skip)]
     graphs: Vec<(image::Rgba<u8>, Vec<step3_group::GraphMultiNode>)>,
+    #[serde(skip)]
     cropped_with_plots: Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
-    plots: Vec<ImageRgba>,
+    #[serde(skip)]
+    cropped_with_confidence: Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
+    curves: Vec<Curve>,
+    curve_output: CurveOutput,
+    resampling: Option<Resampling>,
+    x_format: AxisFormat,
+    y_format: AxisFormat,
 }
 impl LineDetected {
     pub fn save<P: AsRef<std::path::Path>>(&self, output_folder: P) -> image::ImageResult<()> {
         self.save_internal(output_folder.as_ref())
     }
     fn save_internal(&self, output_folder: &Path) -> image::ImageResult<()> {
+        for debug_image in self.debug_images() {
+            debug_image
+                .image
+                .save(output_folder.join(debug_image.file_name()))?;
+        }
+        Ok(())
+    }
+
+    /// Intermediate images of all steps, e.g. to tune the [`Settings`], see [`LineDetected::save`]
+    /// for writing them to files
+    pub fn debug_images(&self) -> Vec<DebugImage<'_>> {
         let Self {
             cropped,
+            gridlines_removed,
+            legend,
+            legend_removed,
             colors: _,
+            background: _,
             color_filtered,
+            shape_filtered_image,
             grouped_image,
             stitched_image,
             aggregated_image,
+            marker_image,
+            markers: _,
+            bar_image,
+            bars: _,
+            error_bar_image,
+            error_bar_pixels: _,
             remaining_vertices: _,
             graphs: _,
             cropped_with_plots: image_with_plots,
-            plots: _,
+            cropped_with_confidence: image_with_confidence,
+            curves: _,
+            curve_output: _,
+            resampling: _,
+            x_format: _,
+            y_format: _,
         } = self;
-        if let Some(cropped) = cropped {
-            cropped.save(output_folder.join("step0_cropped.png"))?;
-        }
-        for (index, color_filtered) in color_filtered.iter().enumerate() {
-            color_filtered.save(output_folder.join(format!("step2_{index}_color_filtered.png")))?;
-        }
-        for (index, grouped_image) in grouped_image.iter().enumerate() {
-            grouped_image
-                .save(output_folder.join(format!("step3_{index}_large_components.png")))?;
-        }
-        for (index, stitched_image) in stitched_image.iter().enumerate() {
-            stitched_image.save(output_folder.join(format!("step4_{index}_stitched.png")))?;
+        fn whole<'a>(
+            step: &'static str,
+            name: &'static str,
+            image: &'a Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
+        ) -> Option<DebugImage<'a>> {
+            image.as_ref().map(|image| DebugImage {
+                step,
+                name,
+                color: None,
+                index: None,
+                image: DebugImageBuffer::Rgba(image),
+            })
         }
-        for (index, aggregated_image) in aggregated_image.iter().enumerate() {
-            aggregated_image.save(output_folder.join(format!("step5_{index}_aggregate.png")))?;
+        fn per_color<'a, T>(
+            step: &'static str,
+            name: &'static str,
+            images: &'a [(image::Rgba<u8>, T)],
+            buffer: fn(&'a T) -> DebugImageBuffer<'a>,
+        ) -> impl Iterator<Item = DebugImage<'a>> {
+            images
+                .iter()
+                .enumerate()
+                .map(move |(index, (color, image))| DebugImage {
+                    step,
+                    name,
+                    color: Some(*color),
+                    index: Some(index),
+                    image: buffer(image),
+                })
         }
-        if let Some(image_with_plots) = image_with_plots {
-            image_with_plots.save(output_folder.join("final_image_with_plots.png"))?;
+        use DebugImageBuffer::{Mask, Rgba};
+        let legend_labels = legend
+            .iter()
+            .flat_map(|legend| &legend.entries)
+            .enumerate()
+            .map(|(index, entry)| DebugImage {
+                step: "step1_legend",
+                name: "label",
+                color: Some(entry.color),
+                index: Some(index),
+                image: Rgba(&entry.label),
+            });
+        whole("step0", "cropped", cropped)
+            .into_iter()
+            .chain(whole("step0", "gridlines_removed", gridlines_removed))
+            .chain(whole("step1", "legend_removed", legend_removed))
+            .chain(legend_labels)
+            .chain(per_color("step2", "color_filtered", color_filtered, Mask))
+            .chain(per_color(
+                "step2",
+                "shape_filtered",
+                shape_filtered_image,
+                Mask,
+            ))
+            .chain(per_color("step3", "error_bars", error_bar_image, Rgba))
+            .chain(per_color("step3", "large_components", grouped_image, Rgba))
+            .chain(per_color("step3", "markers", marker_image, Rgba))
+            .chain(per_color("step3", "bars", bar_image, Rgba))
+            .chain(per_color("step4", "stitched", stitched_image, Rgba))
+            .chain(per_color("step5", "aggregate", aggregated_image, Rgba))
+            .chain(whole("final", "image_with_plots", image_with_plots))
+            .chain(whole(
+                "final",
+                "image_with_confidence",
+                image_with_confidence,
+            ))
+            .collect()
+    }
+
+    /// All plots in the same order as the columns of the csv: graphs, markers and then bars
+    pub fn curves(&self) -> &[Curve] {
+        &self.curves
+    }
+
+    /// Step 0
+    pub fn cropped_image(&self) -> Option<&image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
+        self.cropped.as_ref()
+    }
+
+    /// Step 0, if enabled
+    pub fn gridlines_removed_image(&self) -> Option<&image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
+        self.gridlines_removed.as_ref()
+    }
+
+    /// Step 1, if the legend is enabled and found
+    pub fn legend_removed_image(&self) -> Option<&image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
+        self.legend_removed.as_ref()
+    }
+
+    /// Step 2, per used color
+    pub fn color_filtered_images(&self) -> &[ColorMask] {
+        &self.color_filtered
+    }
+
+    /// Step 2, per used color of monochrome plots
+    pub fn shape_filtered_images(&self) -> &[ColorMask] {
+        &self.shape_filtered_image
+    }
+
+    /// Step 3, per color with error bars
+    pub fn error_bar_images(&self) -> &[ColorImage] {
+        &self.error_bar_image
+    }
+
+    /// Step 3, per color in lines mode
+    pub fn large_component_images(&self) -> &[ColorImage] {
+        &self.grouped_image
+    }
+
+    /// Step 3, per color in scatter mode
+    pub fn marker_images(&self) -> &[ColorImage] {
+        &self.marker_image
+    }
+
+    /// Step 3, per color in bar mode
+    pub fn bar_images(&self) -> &[ColorImage] {
+        &self.bar_image
+    }
+
+    /// Step 4, per color in lines mode
+    pub fn stitched_images(&self) -> &[ColorImage] {
+        &self.stitched_image
+    }
+
+    /// Step 5, per color with graphs
+    pub fn aggregated_images(&self) -> &[ColorImage] {
+        &self.aggregated_image
+    }
+
+    /// Colors which were used for detection, with the number of pixels assigned to them in step 1
+    pub fn colors(&self) -> &[PaletteColor] {
+        self.colors.as_deref().unwrap_or_default()
+    }
+
+    /// Background which was excluded from the curve colors, either set or detected
+    pub fn background(&self) -> [u8; 3] {
+        self.background
+    }
+
+    /// Legend which was excluded from detection, if enabled and found
+    pub fn legend(&self) -> Option<&Legend> {
+        self.legend.as_ref()
+    }
+
+    /// Names per plot, used as csv headers, `Graph #n` unless renamed
+    pub fn names(&self) -> Vec<&str> {
+        self.curves
+            .iter()
+            .map(|curve| curve.name.as_str())
+            .collect()
+    }
+
+    /// The csv separator `;` within the name is replaced by `,`
+    pub fn set_name(&mut self, plot_index: usize, name: String) {
+        if let Some(curve) = self.curves.get_mut(plot_index) {
+            curve.name = name.replace(';', ",");
         }
+    }
 
-        Ok(())
+    /// Legend entry of the plot's color per plot
+    /// Plots of the same color, e.g. in monochrome plots, get the entries of that color in order.
+    pub fn legend_entries(&self) -> Vec<Option<&LegendEntry>> {
+        self.curves
+            .iter()
+            .map(|curve| {
+                let entry = curve.legend_entry?;
+                self.legend.as_ref().map(|legend| &legend.entries[entry])
+            })
+            .collect()
+    }
+
+    /// Markers per color, detected in scatter mode, sorted by x
+    pub fn markers(&self) -> &[(image::Rgba<u8>, Vec<Marker>)] {
+        &self.markers
+    }
+
+    /// Bars per color, detected in bar mode, sorted by position
+    pub fn bars(&self) -> &[(image::Rgba<u8>, Vec<Bar>)] {
+        &self.bars
+    }
+
+    /// One line per bar: color, center, width, value and base
+    pub fn bars_as_csv(&self) -> String {
+        let mut lines = vec!["color;center;width;value;base".to_string()];
+        for (image::Rgba([r, g, b, _]), bars) in &self.bars {
+            for Bar {
+                orientation,
+                center,
+                width,
+                value,
+                base,
+                pixel_rect: _,
+                pixel_end: _,
+            } in bars
+            {
+                let (position_format, value_format) = match orientation {
+                    BarOrientation::Vertical => (self.x_format, self.y_format),
+                    BarOrientation::Horizontal => (self.y_format, self.x_format),
+                };
+                lines.push(format!(
+                    "#{r:02x}{g:02x}{b:02x};{};{width};{};{}",
+                    position_format.format(*center),
+                    value_format.format(*value),
+                    value_format.format(*base),
+                ));
+            }
+        }
+        lines.join("\n")
     }
 
     pub fn final_image_with_plots(&self) -> Option<&image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
         self.cropped_with_plots.as_ref()
     }
 
+    /// The curves colored from red for a low to green for a high confidence
+    pub fn final_image_with_confidence(
+        &self,
+    ) -> Option<&image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
+        self.cropped_with_confidence.as_ref()
+    }
+
+    /// Polylines are exported with separate x and y columns per graph, one row per sample,
+    /// unless resampling onto a common grid is set
     pub fn as_csv(&self) -> String {
-        let mut x_grid = Vec::new();
-        for (_, graph) in &self.plots {
-            x_grid.extend(graph.iter().map(|(x, _)| *x));
-        }
-        x_grid.sort_by(|left, right| {
-            if left < right {
-                std::cmp::Ordering::Less
-            } else if left == right {
-                std::cmp::Ordering::Equal
-            } else {
-                std::cmp::Ordering::Greater
+        if let Some(resampling) = &self.resampling {
+            return self.as_resampled_csv(resampling);
+        }
+        match self.curve_output {
+            CurveOutput::Function => self.as_function_csv(),
+            CurveOutput::Polyline => self.as_polyline_csv(),
+        }
+    }
+
+    /// All plots interpolated onto one grid, one row per x
+    /// Error bars and flags are not resampled.
+    pub fn as_resampled_csv(&self, resampling: &Resampling) -> String {
+        let plots = self
+            .curves
+            .iter()
+            .map(|curve| curve.points.as_slice())
+            .collect_vec();
+        let xs = resample::grid(&resampling.grid, &plots);
+        let columns = plots
+            .iter()
+            .map(|plot| resample::resample(plot, &xs, resampling.interpolation, resampling.outside))
+            .collect_vec();
+        let mut lines = vec![std::iter::once("x").chain(self.names()).join(";")];
+        for (i, x) in xs.iter().enumerate() {
+            let mut line = Vec::with_capacity(columns.len() + 1);
+            line.push(self.x_format.format(*x));
+            for column in &columns {
+                line.push(self.y_format.format(column[i]));
             }
-        });
-        let mut ys = Vec::with_capacity(self.plots.len());
+            lines.push(line.join(";"));
+        }
+        lines.join("\n")
+    }
+
+    fn as_polyline_csv(&self) -> String {
+        let length = self.curves.iter().map(|curve| curve.points.len()).max();
+        let mut columns = Vec::with_capacity(2 * self.curves.len());
+        let mut header = vec!["t".to_string()];
+        for Curve {
+            name,
+            points: graph,
+            interpolated,
+            error_bars,
+            ..
+        } in &self.curves
+        {
+            columns.push((self.x_format, graph.iter().map(|(x, _)| *x).collect_vec()));
+            columns.push((self.y_format, graph.iter().map(|(_, y)| *y).collect_vec()));
+            header.push(format!("{name} x"));
+            header.push(format!("{name} y"));
+            if !error_bars.is_empty() {
+                let mut lower = vec![f64::NAN; graph.len()];
+                let mut upper = vec![f64::NAN; graph.len()];
+                for bar in error_bars {
+                    lower[bar.index] = bar.lower;
+                    upper[bar.index] = bar.upper;
+                }
+                columns.push((self.y_format, lower));
+                columns.push((self.y_format, upper));
+                header.push(format!("{name} lower"));
+                header.push(format!("{name} upper"));
+            }
+            if interpolated.iter().any(|i| *i) {
+                columns.push((
+                    AxisFormat::Number,
+                    interpolated.iter().map(|i| *i as u8 as f64).collect(),
+                ));
+                header.push(format!("{name} interpolated"));
+            }
+        }
+        let mut lines = vec![header.join(";")];
+        for t in 0..length.unwrap_or(0) {
+            let mut line = Vec::with_capacity(columns.len() + 1);
+            line.push(t.to_string());
+            for (format, column) in &columns {
+                line.push(format.format(*column.get(t).unwrap_or(&f64::NAN)));
+            }
+            lines.push(line.join(";"));
+        }
+        lines.join("\n")
+    }
+
+    fn as_function_csv(&self) -> String {
+        let mut x_grid = Vec::new();
+        for curve in &self.curves {
+            x_grid.extend(curve.points.iter().map(|(x, _)| *x));
+        }
+        // plots of reversed x-axes are descending, hence every x is searched in the whole grid
+        x_grid.sort_by(f64::total_cmp);
+        x_grid.dedup();
+        let mut ys = Vec::with_capacity(self.curves.len());
         let mut header = vec!["x".to_string()];
-        for (_, graph) in &self.plots {
-            let mut yy = vec![f32::NAN; x_grid.len()];
-            let mut previous_index = 0;
-            for (x, y) in graph {
-                let index = x_grid
-                    .iter()
-                    .skip(previous_index)
-                    .position(|xx| xx == x)
-                    .unwrap();
-                yy[index + previous_index] = *y;
-                previous_index += index;
+        for Curve {
+            name,
+            points: graph,
+            interpolated,
+            error_bars,
+            ..
+        } in &self.curves
+        {
+            let mut yy = vec![f64::NAN; x_grid.len()];
+            let indices = graph
+                .iter()
+                .map(|(x, _)| x_grid.binary_search_by(|xx| xx.total_cmp(x)).ok())
+                .collect_vec();
+            for ((_, y), index) in graph.iter().zip(&indices) {
+                if let Some(index) = index {
+                    yy[*index] = *y;
+                }
+            }
+            ys.push((self.y_format, yy));
+            header.push(name.clone());
+            if !error_bars.is_empty() {
+                let mut lower = vec![f64::NAN; x_grid.len()];
+                let mut upper = vec![f64::NAN; x_grid.len()];
+                for bar in error_bars {
+                    if let Some(index) = indices[bar.index] {
+                        lower[index] = bar.lower;
+                        upper[index] = bar.upper;
+                    }
+                }
+                ys.push((self.y_format, lower));
+                ys.push((self.y_format, upper));
+                header.push(format!("{name} lower"));
+                header.push(format!("{name} upper"));
+            }
+            if interpolated.iter().any(|i| *i) {
+                let mut flags = vec![f64::NAN; x_grid.len()];
+                for (index, interpolated) in indices.iter().zip(interpolated) {
+                    if let Some(index) = index {
+                        flags[*index] = *interpolated as u8 as f64;
+                    }
+                }
+                ys.push((AxisFormat::Number, flags));
+                header.push(format!("{name} interpolated"));
             }
-            ys.push(yy);
-            header.push(format!("Graph #{}", header.len()));
         }
         let mut lines = vec![header.join(";")];
         for (i, x) in x_grid.into_iter().enumerate() {
-            let mut line = Vec::with_capacity(self.plots.len() + 1);
-            line.push(x.to_string());
-            for yy in &ys {
-                line.push(yy[i].to_string())
+            let mut line = Vec::with_capacity(ys.len() + 1);
+            line.push(self.x_format.format(x));
+            for (format, yy) in &ys {
+                line.push(format.format(yy[i]))
             }
             lines.push(line.join(";"));
         }
         lines.join("\n")
     }
 }
+/// Graphs are combined into one, unless any of them overlap
+fn aggregate_non_overlapping(
+    graphs: Vec<step3_group::GraphMultiNode>,
+) -> Vec<step3_group::GraphMultiNode> {
+    if graphs.iter().enumerate().any(|(i, g1)| {
+        graphs
+            .iter()
+            .enumerate()
+            .any(|(j, g2)| if i == j { false } else { g1.overlaps(g2) })
+    }) {
+        graphs
+    } else {
+        let mut graphs = graphs;
+        if let Some(mut g) = graphs.pop() {
+            for h in graphs {
+                g.aggregate(h);
+            }
+            vec![g]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Whether the mask of a color can contain curves: it spans enough of the width without covering
+/// whole columns, e.g. like the axis frame, and keeps pixels after opening it
+fn is_curve_mask(mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>, settings: &Settings) -> bool {
+    let counts = (0..mask.width())
+        .map(|x| {
+            (0..mask.height())
+                .filter(|y| mask.get_pixel(x, *y) == &HIT)
+                .count()
+        })
+        .collect_vec();
+    if counts
+        .iter()
+        .any(|hits| (*hits as f32 / mask.height() as f32) > settings.height_maximal_fraction())
+        || (step1_color_extraction::covered_width(
+            counts.iter().map(|hits| hits > &0),
+            settings.max_dash_gap(),
+        ) as f32
+            / mask.width() as f32)
+            < settings.width_minimal_fraction()
+    {
+        return false;
+    }
+    let mut opened = mask.clone();
+    for _ in 0..settings.step1_close_count {
+        opened = imageproc::morphology::open(&opened, imageproc::distance_transform::Norm::LInf, 1);
+        opened
+            .iter_mut()
+            .zip(mask.iter())
+            .for_each(|(a, b)| *a = (*a).min(*b));
+    }
+    opened.iter().any(|&p| p != 0)
+}
+
+/// Despiking, smoothing and simplification of a curve, its flags and error bars follow the points
+fn postprocess(curve: &mut Curve, mapping: &dyn Mapping, settings: &Settings) {
+    let pixels = curve
+        .pixel_points
+        .iter()
+        .map(|&(x, y)| (x as f64, y as f64))
+        .collect_vec();
+    let both = settings.step5_curve_output == CurveOutput::Polyline;
+    let pixels = step6_postprocessing::filter(&pixels, both, settings);
+    let points = pixels
+        .iter()
+        .map(|&(x, y)| mapping.map(x as f32, y as f32))
+        .collect_vec();
+    let mut has_error_bar = vec![false; points.len()];
+    for error_bar in &curve.error_bars {
+        has_error_bar[error_bar.index] = true;
+    }
+    let kept = if settings.step6_simplify_tolerance > 0. {
+        step6_postprocessing::simplify(&points, settings.step6_simplify_tolerance, &has_error_bar)
+    } else {
+        (0..points.len()).collect()
+    };
+    for error_bar in &mut curve.error_bars {
+        (error_bar.x, error_bar.y) = points[error_bar.index];
+        // points with an error bar are always kept
+        error_bar.index = kept.partition_point(|&index| index < error_bar.index);
+    }
+    curve.points = kept.iter().map(|&index| points[index]).collect();
+    curve.pixel_points = kept
+        .iter()
+        .map(|&index| (pixels[index].0 as f32, pixels[index].1 as f32))
+        .collect();
+    curve.interpolated = kept
+        .iter()
+        .map(|&index| curve.interpolated[index])
+        .collect();
+    curve.confidence = kept.iter().map(|&index| curve.confidence[index]).collect();
+}
+
 pub fn line_detection(
     image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
     settings: &Settings,
     quadrilateral: UnitQuadrilateral,
     steps_x: u32,
     steps_y: u32,
-    x_limits: (f32, f32),
-    y_limits: (f32, f32),
+    axes: &AxesMapping,
+) -> Result<LineDetected, Error> {
+    Pipeline::default().line_detection(image, settings, quadrilateral, steps_x, steps_y, axes)
+}
+
+fn detect(
+    pipeline: &Pipeline,
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    settings: &Settings,
+    quadrilateral: UnitQuadrilateral,
+    steps_x: u32,
+    steps_y: u32,
+    axes: &AxesMapping,
 ) -> Result<LineDetected, Error> {
     if steps_x < 100 || steps_y < 100 {
         return Err(Error::StepSettingsInvalid { steps_x, steps_y });
     }
-    let cropped = step0_crop::ImageInterpolate::crop(image, quadrilateral, steps_x, steps_y);
+    let crop = pipeline
+        .crop
+        .crop(image, quadrilateral, axes, settings, steps_x, steps_y)?;
+    let mapping = pipeline.mapping.prepare(axes, &crop)?;
+    let cropped = pipeline.crop.resample(image, &crop);
 
     if cropped.width() < 100 && cropped.height() < 100 {
         return Err(Error::CroppedImageToSmall {
@@ -186,218 +1488,438 @@ pub fn line_detection(
         ..Default::default()
     };
     let cropped = line_detected.cropped.as_ref().unwrap();
-    // step 1 - extract colors
-    let colors = step1_color_extraction::extract_colors(cropped, settings);
-    //line_detected.colors = Some(colors);
-    //let colors = line_detected.colors.as_ref().unwrap();
+    let background = settings
+        .step1_background
+        .unwrap_or_else(|| step1_color_extraction::detect_background(cropped));
+    line_detected.background = background;
+    let settings = &Settings {
+        step1_background: Some(background),
+        ..settings.clone()
+    };
+    // step 1 - extract colors, before the gridlines are removed to keep lines in these colors
+    let colors = pipeline.palette.extract_colors(cropped, settings);
+    // step 0 - remove gridlines and the axis frame, the plots are drawn onto the original crop
+    line_detected.gridlines_removed = pipeline
+        .gridlines
+        .remove_gridlines(cropped, &colors, settings);
+    let cropped = line_detected.gridlines_removed.as_ref().unwrap_or(cropped);
+    // step 1 - remove the legend, its swatches have the colors of the curves
+    line_detected.legend = pipeline.legend.detect_legend(cropped, &colors, settings);
+    line_detected.legend_removed = line_detected
+        .legend
+        .as_ref()
+        .map(|legend| pipeline.legend.remove(cropped, legend, settings));
+    let cropped = line_detected.legend_removed.as_ref().unwrap_or(cropped);
+    let excluded = pipeline.shape_filter.excluded(cropped, settings);
     let mut colors_to_use = Vec::new();
-    for color in colors {
-        const H: u8 = 255;
-        const M: u8 = 128;
-        const N: u8 = 0;
-
+    for palette_color in colors {
+        let color = palette_color.color;
         // step 2 - filter colors
-        let color_filtered = step2_color_filtering::color_filtering(cropped, &color, settings);
-        let counts = (0..color_filtered.width())
-            .map(|x| {
-                (0..color_filtered.height())
-                    .filter(|y| color_filtered.get_pixel(x, *y) == &HIT)
-                    .count()
-            })
-            .collect_vec();
-        if counts.iter().any(|hits| {
-            (*hits as f32 / color_filtered.height() as f32) > settings.step1_height_maximal_fraction
-        }) || (counts.iter().filter(|hits| hits > &&0).count() as f32
-            / color_filtered.width() as f32)
-            < settings.step1_width_minimial_fraction
-        {
+        let color_filtered = pipeline.mask.mask(cropped, &color, settings);
+        let shape_filtered = if let Some(excluded) = &excluded {
+            let shape_filtered = pipeline.shape_filter.remove(&color_filtered, excluded);
+            line_detected
+                .shape_filtered_image
+                .push((color, shape_filtered.clone()));
+            shape_filtered
+        } else {
+            color_filtered.clone()
+        };
+        // step 3 - remove error bars, otherwise they are part of the curves
+        let (cleaned, error_bar_pixels) = pipeline
+            .error_bars
+            .remove_error_bars(&shape_filtered, settings);
+        if !is_curve_mask(&cleaned, settings) {
             continue;
         }
-        {
-            let mut opened = color_filtered.clone();
-            for _ in 0..settings.step1_close_count {
-                opened = imageproc::morphology::open(
-                    &opened,
-                    imageproc::distance_transform::Norm::LInf,
-                    1,
-                );
-                opened
-                    .iter_mut()
-                    .zip(color_filtered.iter())
-                    .for_each(|(a, b)| *a = (*a).min(*b));
-            }
-            if opened.iter().all(|&p| p == 0) {
-                continue;
+        line_detected.color_filtered.push((color, color_filtered));
+        let color_filtered = &cleaned;
+
+        colors_to_use.push(palette_color);
+        if !error_bar_pixels.is_empty() {
+            line_detected
+                .error_bar_image
+                .push((color, error_bar_preview(color_filtered, &error_bar_pixels)));
+            line_detected
+                .error_bar_pixels
+                .push((color, error_bar_pixels));
+        }
+        if settings.mode == DetectionMode::Bars {
+            // step 3 - bars instead of lines
+            let bar_pixels = pipeline.bars.find_bars(color_filtered, settings);
+            let orientation = settings.step3_bar_orientation;
+            line_detected
+                .bar_image
+                .push((color, bar_preview(color_filtered, &bar_pixels, orientation)));
+            let baseline = pipeline.bars.baseline(&bar_pixels, settings);
+            let bars = bar_pixels
+                .iter()
+                .map(|bar| Bar::new(bar, orientation, baseline, mapping.as_ref()))
+                .sorted_by(|a, b| a.center.total_cmp(&b.center))
+                .collect_vec();
+            if !bars.is_empty() {
+                line_detected.bars.push((color, bars));
             }
+            continue;
         }
-        line_detected.color_filtered.push(color_filtered);
-        let color_filtered = line_detected.color_filtered.last().unwrap();
-
-        colors_to_use.push(color);
-        // step 3 - group into large components and remaining
-        let (large_components, mut remaining_verticals) = {
-            let (large_components, remaining_verticals) =
-                step3_group::group_large_components_and_remaining(color_filtered, settings);
-
-            let mut grouped_image = imageproc::map::map_colors(color_filtered, |c| {
-                if c == image::Luma([H; 1]) {
-                    image::Rgba([N, N, M, H])
-                } else {
-                    image::Rgba([N, N, N, H])
-                }
-            });
-            for (color_index, component) in large_components.iter().enumerate() {
-                let color = match color_index % 7 {
-                    0 => image::Rgba([H, H, H, H]),
-                    1 => image::Rgba([H, H, N, H]),
-                    2 => image::Rgba([N, H, H, H]),
-                    3 => image::Rgba([H, N, H, H]),
-                    4 => image::Rgba([H, N, N, H]),
-                    5 => image::Rgba([N, H, N, H]),
-                    6 => image::Rgba([N, N, H, H]),
-                    _ => unreachable!(),
-                };
-                for (x, y) in component.ys.iter().enumerate() {
-                    if let Some(y) = y.mean() {
-                        *grouped_image.get_pixel_mut(x as _, y) = color;
+        if settings.mode == DetectionMode::Scatter {
+            // step 3 - markers instead of lines
+            let blobs = pipeline.markers.find_markers(color_filtered, settings);
+            line_detected
+                .marker_image
+                .push((color, marker_preview(color_filtered, &blobs)));
+            let mut markers = blobs
+                .iter()
+                .map(|blob| {
+                    let (x, y) = mapping.map(blob.x, blob.y);
+                    Marker {
+                        x,
+                        y,
+                        pixel_x: blob.x,
+                        pixel_y: blob.y,
+                        pixel_count: blob.pixel_count,
                     }
-                }
-            }
-            for vertical in &remaining_verticals {
-                let step3_group::CombinedVerticals { x_start, combined } = vertical;
-                for (x_offset, ys) in combined.iter().enumerate() {
-                    let x = x_start.0 + x_offset as u32;
-                    let y = ys.mean();
-                    let color = image::Rgba([M, M, M, H]);
-                    *grouped_image.get_pixel_mut(x as _, y) = color;
-                }
+                })
+                .collect_vec();
+            markers.sort_by(|a, b| a.x.total_cmp(&b.x));
+            // a few blobs of a color are usually anti-aliasing artifacts at marker edges
+            if markers.len() >= (settings.step3_marker_min_count as usize).max(1) {
+                line_detected.markers.push((color, markers));
             }
-            line_detected.grouped_image.push(grouped_image);
-
-            (large_components, remaining_verticals)
-        };
+            continue;
+        }
+        // step 3 - group into large components and remaining
+        let (large_components, mut remaining_verticals) =
+            pipeline
+                .grouping
+                .group(color_filtered, cropped, &color, settings);
+        line_detected.grouped_image.push((
+            color,
+            graph_preview(color_filtered, &large_components, &remaining_verticals),
+        ));
         // step 4 - combine components/remaining
-        let graphs = {
-            let graphs = step4_stitch::stitch(
-                large_components,
-                &mut remaining_verticals,
-                settings,
-                color_filtered,
-            );
-
-            let mut stitched_image = imageproc::map::map_colors(color_filtered, |c| {
-                if c == image::Luma([H; 1]) {
-                    image::Rgba([N, N, M, H])
-                } else {
-                    image::Rgba([N, N, N, H])
-                }
-            });
-            for (color_index, graph) in graphs.iter().enumerate() {
-                let color = match color_index % 7 {
-                    0 => image::Rgba([H, H, H, H]),
-                    1 => image::Rgba([H, H, N, H]),
-                    2 => image::Rgba([N, H, H, H]),
-                    3 => image::Rgba([H, N, H, H]),
-                    4 => image::Rgba([H, N, N, H]),
-                    5 => image::Rgba([N, H, N, H]),
-                    6 => image::Rgba([N, N, H, H]),
-                    _ => unreachable!(),
-                };
-                for (x, y) in graph.ys.iter().enumerate() {
-                    if let Some(y) = y.mean() {
-                        *stitched_image.get_pixel_mut(x as _, y) = color;
-                    }
-                }
-            }
-            line_detected.stitched_image.push(stitched_image);
-            line_detected.remaining_vertices.push(remaining_verticals);
-            graphs
-        };
+        let graphs = pipeline.stitching.stitch(
+            large_components,
+            &mut remaining_verticals,
+            color_filtered,
+            settings,
+        );
+        line_detected
+            .stitched_image
+            .push((color, graph_preview(color_filtered, &graphs, &[])));
+        line_detected.remaining_vertices.push(remaining_verticals);
         // step 5 - combine components
-        let graphs = {
-            let aggregate = {
-                if graphs.iter().enumerate().any(|(i, g1)| {
-                    graphs
-                        .iter()
-                        .enumerate()
-                        .any(|(j, g2)| if i == j { false } else { g1.overlaps(g2) })
-                }) {
-                    graphs
-                } else {
-                    let mut graphs = graphs;
-                    if let Some(mut g) = graphs.pop() {
-                        for h in graphs {
-                            g.aggregate(h);
-                        }
-                        vec![g]
-                    } else {
-                        continue;
-                    }
-                }
-            };
-            let mut aggregate_image = imageproc::map::map_colors(color_filtered, |c| {
-                if c == image::Luma([H; 1]) {
-                    image::Rgba([N, N, M, H])
-                } else {
-                    image::Rgba([N, N, N, H])
-                }
-            });
-            for (color_index, graph) in aggregate.iter().enumerate() {
-                let color = match color_index % 7 {
-                    0 => image::Rgba([H, H, H, H]),
-                    1 => image::Rgba([H, H, N, H]),
-                    2 => image::Rgba([N, H, H, H]),
-                    3 => image::Rgba([H, N, H, H]),
-                    4 => image::Rgba([H, N, N, H]),
-                    5 => image::Rgba([N, H, N, H]),
-                    6 => image::Rgba([N, N, H, H]),
-                    _ => unreachable!(),
-                };
-                for (x, y) in graph.ys.iter().enumerate() {
-                    if let Some(y) = y.mean() {
-                        *aggregate_image.get_pixel_mut(x as _, y) = color;
-                    }
-                }
-            }
-
-            line_detected.aggregated_image.push(aggregate_image);
-            aggregate
-        };
-        if !graphs.is_empty() {
-            line_detected.graphs.push((color, graphs));
-        }
-    }
-    if let Some(cropped) = &line_detected.cropped {
-        if !line_detected.graphs.is_empty() {
-            let mut image_with_plots = cropped.clone();
-            for (color, graphs) in &line_detected.graphs {
-                let color = if let Some([r, g, b]) = settings.step6_fit_graph_color {
-                    image::Rgba([r, g, b, 255])
-                } else {
-                    *color
-                };
-                for graph in graphs {
-                    for (x, y) in graph.ys.iter().enumerate() {
-                        if let Some(y) = y.mean() {
-                            *image_with_plots.get_pixel_mut(x as _, y) = color;
-                        }
-                    }
-                }
-            }
-            line_detected.cropped_with_plots = Some(image_with_plots);
+        let graphs = pipeline.aggregation.aggregate(graphs, settings);
+        if graphs.is_empty() {
+            continue;
         }
+        line_detected
+            .aggregated_image
+            .push((color, graph_preview(color_filtered, &graphs, &[])));
+        line_detected.graphs.push((color, graphs));
     }
+    line_detected.cropped_with_plots = plots_image(&line_detected, settings);
     line_detected.colors = Some(colors_to_use);
-    line_detected.plots = line_detected
+    line_detected.curve_output = settings.step5_curve_output;
+    line_detected.resampling = settings.resampling();
+    [line_detected.x_format, line_detected.y_format] = axes.formats();
+    let all_graphs = line_detected
         .graphs
         .iter()
-        .flat_map(|(color, graphs)| {
-            graphs.iter().map(|graph| {
-                let plot = graph.to_plot(x_limits, y_limits, steps_x, steps_y);
-                (*color, plot)
-            })
+        .flat_map(|(_, graphs)| graphs)
+        .collect_vec();
+    let graph_confidence = all_graphs
+        .iter()
+        .map(|graph| {
+            let samples = settings.step5_curve_output.samples(graph);
+            let confidence = pipeline
+                .confidence
+                .confidence(graph, &all_graphs, &samples, settings);
+            (samples, confidence)
         })
-        .collect();
+        .collect_vec();
+    if !all_graphs.is_empty() {
+        line_detected.cropped_with_confidence = line_detected.cropped.as_ref().map(|cropped| {
+            let samples = graph_confidence
+                .iter()
+                .flat_map(|(samples, confidence)| samples.iter().zip(confidence));
+            confidence_image(cropped, samples)
+        });
+    }
+    let error_bar_pixels = &line_detected.error_bar_pixels;
+    let error_bars_of_color = |color: image::Rgba<u8>| {
+        error_bar_pixels
+            .iter()
+            .filter(move |(c, _)| *c == color)
+            .flat_map(|(_, error_bars)| error_bars)
+    };
+    // plots of the same color, e.g. in monochrome plots, get the legend entries of that color in
+    // order
+    let mut used = vec![false; line_detected.legend.as_ref().map_or(0, |l| l.entries.len())];
+    let mut legend_entry = |color: &image::Rgba<u8>| {
+        let legend = line_detected.legend.as_ref()?;
+        let entry = legend.entries.iter().zip(&used).position(|(entry, used)| {
+            !used
+                && settings
+                    .step1_step2_color_metric
+                    .distance(&entry.color, color)
+                    <= settings.step1_step2_color_radius as f32
+        })?;
+        used[entry] = true;
+        Some(entry)
+    };
+    let mapping = mapping.as_ref();
+    let mut curves = Vec::new();
+    let graphs = line_detected
+        .graphs
+        .iter()
+        .flat_map(|(color, graphs)| graphs.iter().map(move |graph| (color, graph)));
+    for ((color, graph), (samples, confidence)) in graphs.zip(graph_confidence) {
+        let error_bars = error_bars_of_color(*color)
+            .filter_map(|error_bar| {
+                let x = error_bar.center_x().round();
+                samples
+                    .iter()
+                    .position(|&(sample_x, y)| sample_x == x && error_bar.contains(sample_x, y))
+                    .map(|index| ErrorBar::new(error_bar, index, samples[index], mapping))
+            })
+            .collect();
+        let mut curve = Curve {
+            name: format!("Graph #{}", curves.len() + 1),
+            color: [color[0], color[1], color[2]],
+            source: CurveSource::Graph,
+            points: samples.iter().map(|&(x, y)| mapping.map(x, y)).collect(),
+            interpolated: samples
+                .iter()
+                .map(|&(x, _)| graph.ys[x as usize].is_interpolated())
+                .collect(),
+            pixel_points: samples,
+            quality: Some(step5_confidence::quality(graph, &confidence)),
+            confidence,
+            line_style: Some(step5_line_style::line_style(graph)),
+            error_bars,
+            legend_entry: legend_entry(color),
+        };
+        // step 6 - filter the curve in pixels before mapping it, markers and bars are kept as they
+        // are
+        pipeline
+            .postprocessing
+            .postprocess(&mut curve, mapping, settings);
+        curves.push(curve);
+    }
+    for (color, markers) in &line_detected.markers {
+        let error_bars = markers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, marker)| {
+                let (x, y) = (marker.pixel_x, marker.pixel_y);
+                error_bars_of_color(*color)
+                    .find(|error_bar| error_bar.contains(x, y))
+                    .map(|error_bar| ErrorBar::new(error_bar, index, (x, y), mapping))
+            })
+            .collect();
+        curves.push(Curve {
+            name: format!("Graph #{}", curves.len() + 1),
+            color: [color[0], color[1], color[2]],
+            source: CurveSource::Markers,
+            points: markers.iter().map(|marker| (marker.x, marker.y)).collect(),
+            pixel_points: markers
+                .iter()
+                .map(|marker| (marker.pixel_x, marker.pixel_y))
+                .collect(),
+            interpolated: vec![false; markers.len()],
+            confidence: vec![1.; markers.len()],
+            line_style: None,
+            quality: None,
+            error_bars,
+            legend_entry: legend_entry(color),
+        });
+    }
+    for (color, bars) in &line_detected.bars {
+        curves.push(Curve {
+            name: format!("Graph #{}", curves.len() + 1),
+            color: [color[0], color[1], color[2]],
+            source: CurveSource::Bars,
+            points: bars.iter().map(|bar| (bar.center, bar.value)).collect(),
+            pixel_points: bars.iter().map(|bar| bar.pixel_end).collect(),
+            interpolated: vec![false; bars.len()],
+            confidence: vec![1.; bars.len()],
+            line_style: None,
+            quality: None,
+            error_bars: Vec::new(),
+            legend_entry: legend_entry(color),
+        });
+    }
+    line_detected.curves = curves;
 
     Ok(line_detected)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_round_trip_without_images() {
+        let image_bytes = include_bytes!("../../Readme_Image_Graph.png");
+        let image = image::load_from_memory(image_bytes).unwrap().to_rgba8();
+        let detected = line_detection(
+            &image,
+            &Settings::default(),
+            UnitQuadrilateral::unit_square(),
+            image.width(),
+            image.height(),
+            &AxesMapping::Limits {
+                x_axis: Axis::linear(1950., 2010.),
+                y_axis: Axis::linear(0., 60.),
+            },
+        )
+        .unwrap();
+        assert!(!detected.curves().is_empty());
+
+        let json = serde_json::to_string(&detected).unwrap();
+        let restored: LineDetected = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert_eq!(restored.as_csv(), detected.as_csv());
+        assert_eq!(restored.bars_as_csv(), detected.bars_as_csv());
+        assert_eq!(restored.colors(), detected.colors());
+        assert!(restored.debug_images().is_empty());
+    }
+
+    fn readme_detection(x_axis: Axis, y_axis: Axis) -> Result<LineDetected, Error> {
+        let image_bytes = include_bytes!("../../Readme_Image_Graph.png");
+        let image = image::load_from_memory(image_bytes).unwrap().to_rgba8();
+        line_detection(
+            &image,
+            &Settings::default(),
+            UnitQuadrilateral::unit_square(),
+            image.width(),
+            image.height(),
+            &AxesMapping::Limits { x_axis, y_axis },
+        )
+    }
+
+    #[test]
+    fn reversed_axes_mirror_the_values() {
+        let detected = readme_detection(Axis::linear(1950., 2010.), Axis::linear(0., 60.)).unwrap();
+        let reversed = readme_detection(Axis::linear(2010., 1950.), Axis::linear(60., 0.)).unwrap();
+        let points = &detected.curves()[0].points;
+        let reversed_points = &reversed.curves()[0].points;
+        assert_eq!(points.len(), reversed_points.len());
+        for ((x, y), (reversed_x, reversed_y)) in points.iter().zip(reversed_points) {
+            assert!((x - (3960. - reversed_x)).abs() < 1e-6, "{x} {reversed_x}");
+            assert!((y - (60. - reversed_y)).abs() < 1e-6, "{y} {reversed_y}");
+        }
+    }
+
+    #[test]
+    fn invalid_axes_are_errors() {
+        let error = readme_detection(Axis::linear(1950., 1950.), Axis::linear(0., 60.))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Axis limits 1950 and 1950 must be finite and different"
+        );
+    }
+
+    /// The whole image with as many steps as pixels
+    struct WholeImage;
+    impl CropStage for WholeImage {
+        fn crop(
+            &self,
+            image: &image::RgbaImage,
+            _quadrilateral: UnitQuadrilateral,
+            _axes: &AxesMapping,
+            _settings: &Settings,
+            _steps_x: u32,
+            _steps_y: u32,
+        ) -> Result<Crop, Error> {
+            Crop::new(
+                UnitQuadrilateral::unit_square(),
+                CropMode::Bilinear,
+                image.width(),
+                image.height(),
+            )
+        }
+    }
+
+    #[test]
+    fn too_small_crops_are_errors() {
+        let image = image::RgbaImage::from_pixel(50, 40, image::Rgba([255, 255, 255, 255]));
+        let axes = AxesMapping::Limits {
+            x_axis: Axis::linear(0., 1.),
+            y_axis: Axis::linear(0., 1.),
+        };
+        let quadrilateral = UnitQuadrilateral::unit_square();
+        let settings = Settings::default();
+        let error = line_detection(&image, &settings, quadrilateral, 50, 40, &axes)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Cropped image of 50x40 pixels is too small, a crop requires at least 2x2 and the \
+            detection 100x100"
+        );
+        let pipeline = Pipeline {
+            crop: Box::new(WholeImage),
+            ..Default::default()
+        };
+        let error = pipeline
+            .line_detection(&image, &settings, quadrilateral, 100, 100, &axes)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Cropped image of 50x40 pixels is too small, at least 100 pixels in one direction are \
+            required"
+        );
+    }
+
+    /// Pixel positions relative to the baseline at the bottom edge of row 59
+    struct AboveRow59;
+    impl Mapping for AboveRow59 {
+        fn map(&self, x: f32, y: f32) -> (f64, f64) {
+            (x as f64, (59.5 - y) as f64)
+        }
+    }
+
+    #[test]
+    fn negative_bars_end_at_their_bottom() {
+        let mask = image::GrayImage::from_fn(60, 100, |x, y| {
+            let hit = match x {
+                5..=14 => (30..60).contains(&y),
+                25..=34 => (40..60).contains(&y),
+                45..=54 => (60..80).contains(&y),
+                _ => false,
+            };
+            if hit {
+                HIT
+            } else {
+                MISSED
+            }
+        });
+        let settings = Settings {
+            mode: DetectionMode::Bars,
+            ..Default::default()
+        };
+        let bar_pixels = step3_bars::find_bars(&mask, &settings);
+        assert_eq!(bar_pixels.len(), 3);
+        let orientation = BarOrientation::Vertical;
+        let baseline = step3_bars::baseline(&bar_pixels, orientation);
+        assert_eq!(baseline, Some(59.5));
+        let bars = bar_pixels
+            .iter()
+            .map(|bar| Bar::new(bar, orientation, baseline, &AboveRow59))
+            .map(|bar| (bar.value, bar.base, bar.pixel_end))
+            .collect_vec();
+        assert_eq!(
+            bars,
+            [
+                (30., 0., (9.5, 29.5)),
+                (20., 0., (29.5, 39.5)),
+                (-20., 0., (49.5, 79.5))
+            ]
+        );
+    }
+}
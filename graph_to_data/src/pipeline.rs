@@ -0,0 +1,472 @@
+use crate::{
+    axis::PixelMapping,
+    step0_crop::{Crop, ImageInterpolate},
+    step0_gridlines, step1_color_extraction, step1_legend, step2_color_filtering,
+    step2_shape_filtering, step3_bars,
+    step3_bars::BarPixels,
+    step3_error_bars,
+    step3_error_bars::ErrorBarPixels,
+    step3_group,
+    step3_group::{CombinedVerticals, GraphMultiNode},
+    step3_scatter,
+    step3_scatter::Blob,
+    step4_dashes, step4_stitch, step5_confidence, step5_line_style, step5_polyline, AxesMapping,
+    Curve, CurveOutput, Error, Legend, LineDetected, PaletteColor, Settings, UnitQuadrilateral,
+};
+use itertools::Itertools;
+
+/// Step 0 - crops (and straightens or unwraps) the plot area of the image into a raster of
+/// `steps_x` times `steps_y` pixels
+pub trait CropStage: Send + Sync {
+    fn crop(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        quadrilateral: UnitQuadrilateral,
+        axes: &AxesMapping,
+        settings: &Settings,
+        steps_x: u32,
+        steps_y: u32,
+    ) -> Result<Crop, Error>;
+
+    fn resample(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        crop: &Crop,
+    ) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+        ImageInterpolate::crop(image, crop)
+    }
+}
+
+/// Step 0 - replaces gridlines and the axis frame by the background, None if nothing is removed
+/// Lines in the colors of the curves are kept.
+pub trait GridlineStage: Send + Sync {
+    fn remove_gridlines(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        colors: &[PaletteColor],
+        settings: &Settings,
+    ) -> Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>;
+}
+
+/// Step 1 - finds the colors of the curves, the background is already known
+/// It runs on the cropped image before the gridlines are removed, lines in these colors are kept.
+pub trait PaletteStage: Send + Sync {
+    fn extract_colors(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<PaletteColor>;
+}
+
+/// Step 1 - finds the legend, which is then replaced by the background
+pub trait LegendStage: Send + Sync {
+    fn detect_legend(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        colors: &[PaletteColor],
+        settings: &Settings,
+    ) -> Option<Legend>;
+
+    fn remove(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        legend: &Legend,
+        settings: &Settings,
+    ) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+        step1_legend::remove(image, legend, settings.background())
+    }
+}
+
+/// Step 2 - marks the pixels of one color with [`crate::HIT`], all others with [`crate::MISSED`]
+pub trait MaskStage: Send + Sync {
+    fn mask(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        color: &image::Rgba<u8>,
+        settings: &Settings,
+    ) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>>;
+}
+
+/// Step 2 - finds pixels which belong to no curve by their shape, e.g. the frame and text of
+/// monochrome plots, None if the masks are kept as they are
+pub trait ShapeFilterStage: Send + Sync {
+    fn excluded(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Option<image::ImageBuffer<image::Luma<u8>, Vec<u8>>>;
+
+    /// Removes the excluded pixels from the mask of one color
+    fn remove(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        excluded: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    ) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>> {
+        step2_shape_filtering::remove(mask, excluded)
+    }
+}
+
+/// Step 3 - removes error bars from the mask of one color, they are attached to the points of the
+/// curves and markers afterwards
+pub trait ErrorBarStage: Send + Sync {
+    fn remove_error_bars(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> (
+        image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        Vec<ErrorBarPixels>,
+    );
+}
+
+/// Step 3 - finds the bars of one color, replaces steps 3 to 5 in [`crate::DetectionMode::Bars`]
+pub trait BarStage: Send + Sync {
+    fn find_bars(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<BarPixels>;
+
+    /// Edge shared by the bars along the value axis, the values are taken at the other edge
+    fn baseline(&self, bars: &[BarPixels], settings: &Settings) -> Option<f32> {
+        step3_bars::baseline(bars, settings.step3_bar_orientation)
+    }
+}
+
+/// Step 3 - finds the markers of one color, replaces steps 3 to 5 in
+/// [`crate::DetectionMode::Scatter`]
+pub trait MarkerStage: Send + Sync {
+    fn find_markers(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<Blob>;
+}
+
+/// Step 3 - splits the mask into components spanning large parts of the width and the remaining
+/// vertical runs, the image and color allow sub-pixel positions of the runs
+pub trait GroupingStage: Send + Sync {
+    fn group(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        color: &image::Rgba<u8>,
+        settings: &Settings,
+    ) -> (Vec<GraphMultiNode>, Vec<CombinedVerticals>);
+}
+
+/// Step 4 - extends the large components by the remaining verticals and joins them,
+/// consumed verticals are removed from `remaining`
+pub trait StitchingStage: Send + Sync {
+    fn stitch(
+        &self,
+        components: Vec<GraphMultiNode>,
+        remaining: &mut Vec<CombinedVerticals>,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<GraphMultiNode>;
+}
+
+/// Step 5 - combines the stitched graphs of one color into the final curves
+pub trait AggregationStage: Send + Sync {
+    fn aggregate(&self, graphs: Vec<GraphMultiNode>, settings: &Settings) -> Vec<GraphMultiNode>;
+}
+
+/// Step 5 - confidence between 0 and 1 of each sample of a graph, `all_graphs` contains the graphs
+/// of all colors
+pub trait ConfidenceStage: Send + Sync {
+    fn confidence(
+        &self,
+        graph: &GraphMultiNode,
+        all_graphs: &[&GraphMultiNode],
+        samples: &[(f32, f32)],
+        settings: &Settings,
+    ) -> Vec<f32>;
+}
+
+/// Step 6 - filters the pixel points of a curve traced by steps 3 to 5 and maps them to its points,
+/// markers and bars are kept as they are
+pub trait PostprocessingStage: Send + Sync {
+    fn postprocess(&self, curve: &mut Curve, mapping: &dyn Mapping, settings: &Settings);
+}
+
+/// Converts pixels of the cropped image to data values
+pub trait Mapping: Send + Sync {
+    fn map(&self, x: f32, y: f32) -> (f64, f64);
+}
+
+/// Prepares the [`Mapping`] of the axes for a crop
+pub trait MappingStage: Send + Sync {
+    fn prepare(&self, axes: &AxesMapping, crop: &Crop) -> Result<Box<dyn Mapping>, Error>;
+}
+
+pub struct DefaultCrop;
+impl CropStage for DefaultCrop {
+    fn crop(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        quadrilateral: UnitQuadrilateral,
+        axes: &AxesMapping,
+        settings: &Settings,
+        steps_x: u32,
+        steps_y: u32,
+    ) -> Result<Crop, Error> {
+        axes.crop(
+            quadrilateral,
+            settings.step0_crop_mode,
+            [image.width(), image.height()],
+            steps_x,
+            steps_y,
+        )
+    }
+}
+
+pub struct DefaultGridlines;
+impl GridlineStage for DefaultGridlines {
+    fn remove_gridlines(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        colors: &[PaletteColor],
+        settings: &Settings,
+    ) -> Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
+        settings
+            .step0_remove_gridlines
+            .then(|| step0_gridlines::remove_gridlines(image, colors, settings))
+    }
+}
+
+pub struct DefaultPalette;
+impl PaletteStage for DefaultPalette {
+    fn extract_colors(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<PaletteColor> {
+        step1_color_extraction::extract_colors(image, settings)
+    }
+}
+
+pub struct DefaultLegend;
+impl LegendStage for DefaultLegend {
+    fn detect_legend(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        colors: &[PaletteColor],
+        settings: &Settings,
+    ) -> Option<Legend> {
+        if settings.step1_detect_legend {
+            step1_legend::detect_legend(image, colors, settings)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct DefaultMask;
+impl MaskStage for DefaultMask {
+    fn mask(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        color: &image::Rgba<u8>,
+        settings: &Settings,
+    ) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>> {
+        step2_color_filtering::color_filtering(image, color, settings)
+    }
+}
+
+pub struct DefaultShapeFilter;
+impl ShapeFilterStage for DefaultShapeFilter {
+    fn excluded(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Option<image::ImageBuffer<image::Luma<u8>, Vec<u8>>> {
+        settings
+            .step1_monochrome
+            .then(|| step2_shape_filtering::frame_and_text(image, settings))
+    }
+}
+
+pub struct DefaultErrorBars;
+impl ErrorBarStage for DefaultErrorBars {
+    fn remove_error_bars(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> (
+        image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        Vec<ErrorBarPixels>,
+    ) {
+        if settings.detects_error_bars() {
+            step3_error_bars::remove_error_bars(mask, settings)
+        } else {
+            (mask.clone(), Vec::new())
+        }
+    }
+}
+
+pub struct DefaultBars;
+impl BarStage for DefaultBars {
+    fn find_bars(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<BarPixels> {
+        step3_bars::find_bars(mask, settings)
+    }
+}
+
+pub struct DefaultMarkers;
+impl MarkerStage for DefaultMarkers {
+    fn find_markers(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<Blob> {
+        step3_scatter::find_markers(mask, settings)
+    }
+}
+
+pub struct DefaultGrouping;
+impl GroupingStage for DefaultGrouping {
+    fn group(
+        &self,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        color: &image::Rgba<u8>,
+        settings: &Settings,
+    ) -> (Vec<GraphMultiNode>, Vec<CombinedVerticals>) {
+        let weights = step2_color_filtering::color_weights(image, color, settings);
+        step3_group::group_large_components_and_remaining(mask, &weights, settings)
+    }
+}
+
+pub struct DefaultStitching;
+impl StitchingStage for DefaultStitching {
+    fn stitch(
+        &self,
+        components: Vec<GraphMultiNode>,
+        remaining: &mut Vec<CombinedVerticals>,
+        mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        settings: &Settings,
+    ) -> Vec<GraphMultiNode> {
+        let graphs = step4_stitch::stitch(components, remaining, settings, mask);
+        if settings.step4_bridge_dashes {
+            step4_dashes::bridge(graphs, remaining, settings, mask)
+        } else {
+            graphs
+        }
+    }
+}
+
+pub struct DefaultAggregation;
+impl AggregationStage for DefaultAggregation {
+    fn aggregate(&self, graphs: Vec<GraphMultiNode>, settings: &Settings) -> Vec<GraphMultiNode> {
+        let graphs = if settings.step5_curve_output == CurveOutput::Polyline {
+            step5_polyline::connect_touching(graphs)
+        } else {
+            graphs
+        };
+        // curves of the same color in monochrome plots are distinguished by their line style
+        if settings.step1_monochrome {
+            graphs
+                .into_iter()
+                .into_group_map_by(step5_line_style::line_style)
+                .into_iter()
+                .sorted_by_key(|(style, _)| *style)
+                .flat_map(|(_, graphs)| crate::aggregate_non_overlapping(graphs))
+                .collect_vec()
+        } else {
+            crate::aggregate_non_overlapping(graphs)
+        }
+    }
+}
+
+pub struct DefaultConfidence;
+impl ConfidenceStage for DefaultConfidence {
+    fn confidence(
+        &self,
+        graph: &GraphMultiNode,
+        all_graphs: &[&GraphMultiNode],
+        samples: &[(f32, f32)],
+        _settings: &Settings,
+    ) -> Vec<f32> {
+        step5_confidence::confidence(graph, all_graphs, samples)
+    }
+}
+
+pub struct DefaultPostprocessing;
+impl PostprocessingStage for DefaultPostprocessing {
+    fn postprocess(&self, curve: &mut Curve, mapping: &dyn Mapping, settings: &Settings) {
+        crate::postprocess(curve, mapping, settings)
+    }
+}
+
+impl Mapping for PixelMapping {
+    fn map(&self, x: f32, y: f32) -> (f64, f64) {
+        PixelMapping::map(self, x, y)
+    }
+}
+
+pub struct DefaultMapping;
+impl MappingStage for DefaultMapping {
+    fn prepare(&self, axes: &AxesMapping, crop: &Crop) -> Result<Box<dyn Mapping>, Error> {
+        Ok(Box::new(axes.prepare(crop)?))
+    }
+}
+
+/// The stages of [`crate::line_detection`], each of them can be replaced, e.g.
+/// `Pipeline { mask: Box::new(MyMask), ..Default::default() }`
+/// The default stages are configured by the [`Settings`], e.g. the gridlines are only removed if
+/// `step0_remove_gridlines` is set.
+pub struct Pipeline {
+    pub crop: Box<dyn CropStage>,
+    pub gridlines: Box<dyn GridlineStage>,
+    pub palette: Box<dyn PaletteStage>,
+    pub legend: Box<dyn LegendStage>,
+    pub mask: Box<dyn MaskStage>,
+    pub shape_filter: Box<dyn ShapeFilterStage>,
+    pub error_bars: Box<dyn ErrorBarStage>,
+    pub bars: Box<dyn BarStage>,
+    pub markers: Box<dyn MarkerStage>,
+    pub grouping: Box<dyn GroupingStage>,
+    pub stitching: Box<dyn StitchingStage>,
+    pub aggregation: Box<dyn AggregationStage>,
+    pub confidence: Box<dyn ConfidenceStage>,
+    pub postprocessing: Box<dyn PostprocessingStage>,
+    pub mapping: Box<dyn MappingStage>,
+}
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self {
+            crop: Box::new(DefaultCrop),
+            gridlines: Box::new(DefaultGridlines),
+            palette: Box::new(DefaultPalette),
+            legend: Box::new(DefaultLegend),
+            mask: Box::new(DefaultMask),
+            shape_filter: Box::new(DefaultShapeFilter),
+            error_bars: Box::new(DefaultErrorBars),
+            bars: Box::new(DefaultBars),
+            markers: Box::new(DefaultMarkers),
+            grouping: Box::new(DefaultGrouping),
+            stitching: Box::new(DefaultStitching),
+            aggregation: Box::new(DefaultAggregation),
+            confidence: Box::new(DefaultConfidence),
+            postprocessing: Box::new(DefaultPostprocessing),
+            mapping: Box::new(DefaultMapping),
+        }
+    }
+}
+impl Pipeline {
+    pub fn line_detection(
+        &self,
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        settings: &Settings,
+        quadrilateral: UnitQuadrilateral,
+        steps_x: u32,
+        steps_y: u32,
+        axes: &AxesMapping,
+    ) -> Result<LineDetected, Error> {
+        crate::detect(self, image, settings, quadrilateral, steps_x, steps_y, axes)
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{UnitInterval, UnitPoint, UnitQuadrilateral};
+
+/// Minimal length of axis lines as fraction of image width/height
+const LINE_MIN_FRACTION: f32 = 0.4;
+/// Minimal difference in luminance to the background of axis lines
+const LINE_MIN_CONTRAST: f32 = 64.;
+
+/// Suggests a crop area by searching for the axis frame, i.e. the outermost long horizontal and vertical lines
+/// If there is no right/top frame line, the end of the x-axis/y-axis is used instead
+pub fn detect_plot_area(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+) -> Option<UnitQuadrilateral> {
+    let (width, height) = image.dimensions();
+    if width < 3 || height < 3 {
+        return None;
+    }
+    let mask = line_mask(image);
+    let is_line = |x: u32, y: u32| mask[(y * width + x) as usize];
+
+    let rows = (0..height)
+        .map(|y| longest_run((0..width).map(|x| is_line(x, y))))
+        .collect::<Vec<_>>();
+    let columns = (0..width)
+        .map(|x| longest_run((0..height).map(|y| is_line(x, y))))
+        .collect::<Vec<_>>();
+    let row_groups = long_line_groups(&rows, width);
+    let column_groups = long_line_groups(&columns, height);
+
+    let (first_row, last_row) = (row_groups.first()?, row_groups.last()?);
+    let (first_column, last_column) = (column_groups.first()?, column_groups.last()?);
+    // use the inner edges of the frame lines
+    let left = first_column.1 + 1;
+    let bottom = last_row.0.checked_sub(1)?;
+    let right = if column_groups.len() > 1 {
+        last_column.0.checked_sub(1)?
+    } else {
+        let (start, length) = rows[last_row.1 as usize];
+        start + length - 1
+    };
+    let top = if row_groups.len() > 1 {
+        first_row.1 + 1
+    } else {
+        let (start, _) = columns[first_column.0 as usize];
+        start
+    };
+    if right <= left + 10 || bottom <= top + 10 {
+        return None;
+    }
+    let point = |x: u32, y: u32| UnitPoint {
+        x: UnitInterval(x as f32 / width as f32),
+        y: UnitInterval(y as f32 / height as f32),
+    };
+    Some(UnitQuadrilateral::rectangular(
+        point(left, top),
+        point(right, bottom),
+    ))
+}
+
+/// Marks pixels which differ strongly in luminance from the (median) background
+fn line_mask(image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>) -> Vec<bool> {
+    let luminance = image
+        .pixels()
+        .map(|image::Rgba([r, g, b, _])| 0.299 * *r as f32 + 0.587 * *g as f32 + 0.114 * *b as f32)
+        .collect::<Vec<_>>();
+    let background = {
+        let mut sorted = luminance.clone();
+        sorted.sort_by(f32::total_cmp);
+        sorted[sorted.len() / 2]
+    };
+    luminance
+        .into_iter()
+        .map(|l| (l - background).abs() > LINE_MIN_CONTRAST)
+        .collect()
+}
+
+/// Start and length of the longest run of 'true'
+fn longest_run(values: impl Iterator<Item = bool>) -> (u32, u32) {
+    let mut longest = (0, 0);
+    let mut current = (0, 0);
+    for (i, value) in values.enumerate() {
+        if value {
+            if current.1 == 0 {
+                current.0 = i as u32;
+            }
+            current.1 += 1;
+            if current.1 > longest.1 {
+                longest = current;
+            }
+        } else {
+            current.1 = 0;
+        }
+    }
+    longest
+}
+
+/// Groups of adjacent lines (first and last index) whose longest run is long enough
+fn long_line_groups(runs: &[(u32, u32)], length: u32) -> Vec<(u32, u32)> {
+    let min_length = (LINE_MIN_FRACTION * length as f32) as u32;
+    let mut groups: Vec<(u32, u32)> = Vec::new();
+    for (index, (_, run)) in runs.iter().enumerate() {
+        let index = index as u32;
+        if *run < min_length {
+            continue;
+        }
+        match groups.last_mut() {
+            Some((_, last)) if *last + 1 == index => *last = index,
+            _ => groups.push((index, index)),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 200;
+    const HEIGHT: u32 = 150;
+
+    fn image(is_black: impl Fn(u32, u32) -> bool) -> image::RgbaImage {
+        image::RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+            if is_black(x, y) {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    /// Left, top, right and bottom pixel
+    fn pixels(area: UnitQuadrilateral) -> [u32; 4] {
+        [
+            area.lt.x.0 * WIDTH as f32,
+            area.lt.y.0 * HEIGHT as f32,
+            area.rb.x.0 * WIDTH as f32,
+            area.rb.y.0 * HEIGHT as f32,
+        ]
+        .map(|p| p.round() as u32)
+    }
+
+    #[test]
+    fn frame_gives_its_inner_edges() {
+        let framed = image(|x, y| {
+            let within = (20..=180).contains(&x) && (10..=140).contains(&y);
+            within && (x <= 21 || x == 180 || y == 10 || y >= 139)
+        });
+        let area = detect_plot_area(&framed).unwrap();
+        assert_eq!(pixels(area), [22, 11, 179, 138]);
+    }
+
+    #[test]
+    fn axes_end_the_area_without_frame() {
+        let axes = image(|x, y| {
+            (x == 20 && (30..=140).contains(&y)) || (y == 140 && (20..=170).contains(&x))
+        });
+        let area = detect_plot_area(&axes).unwrap();
+        assert_eq!(pixels(area), [21, 30, 170, 139]);
+    }
+
+    #[test]
+    fn no_lines_no_area() {
+        assert!(detect_plot_area(&image(|_, _| false)).is_none());
+        let short = image(|x, y| y == 70 && (50..100).contains(&x));
+        assert!(detect_plot_area(&short).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{Axis, Error, UnitPoint};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AngleDirection {
+    /// Mathematical convention
+    #[default]
+    CounterClockwise,
+    /// Compass convention, e.g. antenna patterns
+    Clockwise,
+}
+
+/// Polar axes, e.g. antenna patterns or radar charts
+/// The image is unwrapped into a raster with the angle along x (0° to 360°) and the radius along y
+/// (reference circle at the top, center at the bottom), hence circular gridlines become
+/// horizontal lines and curves r(θ) become functions.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PolarCalibration {
+    /// Position of the center in the (uncropped) image
+    pub center: UnitPoint,
+    /// Position on the reference circle, usually the outermost one, it bounds the unwrapped raster
+    pub reference: UnitPoint,
+    /// Value at the center (`min`) and on the reference circle (`max`)
+    pub radius: Axis,
+    /// Direction of 0° in degrees, counterclockwise from the positive x direction of the image,
+    /// e.g. 90. if 0° is at the top
+    pub zero_angle: f32,
+    pub direction: AngleDirection,
+}
+impl PolarCalibration {
+    pub fn check(&self, [width, height]: [u32; 2]) -> Result<(), Error> {
+        self.radius.check()?;
+        if !self.zero_angle.is_finite() {
+            return Err(Error::PolarAngleInvalid {
+                angle: self.zero_angle,
+            });
+        }
+        let radius = self.pixel_radius([width, height]);
+        if !(radius.is_finite() && radius >= 1.) {
+            return Err(Error::PolarRadiusTooSmall { radius });
+        }
+        Ok(())
+    }
+
+    /// Distance between the center and the reference circle in pixels
+    fn pixel_radius(&self, [width, height]: [u32; 2]) -> f32 {
+        let dx = (self.reference.x.0 - self.center.x.0) * width as f32;
+        let dy = (self.reference.y.0 - self.center.y.0) * height as f32;
+        dx.hypot(dy)
+    }
+
+    /// Angle in degrees of a column of the unwrapped raster
+    fn angle(&self, x: f32, steps_x: u32) -> f32 {
+        360. * x / steps_x as f32
+    }
+
+    /// Fraction between 0. (center) and 1. (reference circle) of a row of the unwrapped raster
+    fn radius_fraction(&self, y: f32, steps_y: u32) -> f32 {
+        1. - y / (steps_y - 1) as f32
+    }
+
+    /// Position within the uncropped image (in unit coordinates) of a pixel of the unwrapped raster
+    /// Pixels are square, hence the aspect ratio of the image is needed for circles.
+    pub(crate) fn source_position(
+        &self,
+        x: f32,
+        y: f32,
+        [steps_x, steps_y]: [u32; 2],
+        [width, height]: [u32; 2],
+    ) -> [f32; 2] {
+        let sign = match self.direction {
+            AngleDirection::CounterClockwise => 1.,
+            AngleDirection::Clockwise => -1.,
+        };
+        let angle = (self.zero_angle + sign * self.angle(x, steps_x)).to_radians();
+        let radius = self.radius_fraction(y, steps_y) * self.pixel_radius([width, height]);
+        // the y-axis of the image points downwards
+        [
+            self.center.x.0 + radius * angle.cos() / width as f32,
+            self.center.y.0 - radius * angle.sin() / height as f32,
+        ]
+    }
+
+    /// Maps a pixel of the unwrapped raster to (angle in degrees, radius)
+    pub(crate) fn map(&self, x: f32, y: f32, [steps_x, steps_y]: [u32; 2]) -> (f64, f64) {
+        let angle = self.angle(x, steps_x) as f64;
+        let radius = self
+            .radius
+            .interpolate(self.radius_fraction(y, steps_y) as f64);
+        (angle, radius)
+    }
+}
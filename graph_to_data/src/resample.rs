@@ -0,0 +1,236 @@
+use itertools::Itertools;
+
+use crate::{Interpolation, OutsideRange, ResampleGrid};
+
+/// Steps beyond this count are not generated, e.g. for a tiny step on a large range
+const MAX_GRID_POINTS: usize = 1_000_000;
+
+/// The x values of the grid, ranges of `Count` and `Step` span all plots
+pub fn grid(grid: &ResampleGrid, plots: &[&[(f64, f64)]]) -> Vec<f64> {
+    let (min, max) = plots
+        .iter()
+        .flat_map(|plot| plot.iter().map(|(x, _)| *x))
+        .filter(|x| x.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| {
+            (min.min(x), max.max(x))
+        });
+    match grid {
+        ResampleGrid::Explicit(xs) => xs.clone(),
+        _ if min > max => Vec::new(),
+        ResampleGrid::Count(0) => Vec::new(),
+        ResampleGrid::Count(1) => vec![min],
+        ResampleGrid::Count(count) => {
+            let step = (max - min) / (*count - 1) as f64;
+            (0..*count).map(|i| min + i as f64 * step).collect()
+        }
+        ResampleGrid::Step(step) if *step > 0. => {
+            // the last step may fall short of the maximum by rounding
+            let count = (((max - min) / step + 1e-3).floor() as usize + 1).min(MAX_GRID_POINTS);
+            (0..count).map(|i| min + i as f64 * step).collect()
+        }
+        ResampleGrid::Step(_) => Vec::new(),
+    }
+}
+
+/// Points sorted by x, points with the same x are averaged
+fn prepare(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    points
+        .iter()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .sorted_by(|left, right| left.0.total_cmp(&right.0))
+        .chunk_by(|(x, _)| *x)
+        .into_iter()
+        .map(|(x, group)| {
+            let ys = group.map(|(_, y)| *y).collect_vec();
+            (x, ys.iter().sum::<f64>() / ys.len() as f64)
+        })
+        .collect()
+}
+
+/// Second derivatives of the natural cubic spline, zero at both ends
+fn spline_second_derivatives(points: &[(f64, f64)]) -> Vec<f64> {
+    let n = points.len();
+    let mut second = vec![0.; n];
+    if n < 3 {
+        return second;
+    }
+    // tridiagonal system of the inner points, solved by forward elimination and back substitution
+    let mut diagonal = vec![0.; n];
+    let mut rhs = vec![0.; n];
+    for i in 1..n - 1 {
+        let (h0, h1) = (points[i].0 - points[i - 1].0, points[i + 1].0 - points[i].0);
+        diagonal[i] = 2. * (h0 + h1);
+        rhs[i] = 6. * ((points[i + 1].1 - points[i].1) / h1 - (points[i].1 - points[i - 1].1) / h0);
+        if i > 1 {
+            let factor = h0 / diagonal[i - 1];
+            diagonal[i] -= factor * h0;
+            rhs[i] -= factor * rhs[i - 1];
+        }
+    }
+    for i in (1..n - 1).rev() {
+        let h1 = points[i + 1].0 - points[i].0;
+        second[i] = (rhs[i] - h1 * second[i + 1]) / diagonal[i];
+    }
+    second
+}
+
+/// Values of the curve at each x of the grid
+pub fn resample(
+    points: &[(f64, f64)],
+    xs: &[f64],
+    interpolation: Interpolation,
+    outside: OutsideRange,
+) -> Vec<f64> {
+    let points = prepare(points);
+    let second = match interpolation {
+        Interpolation::Linear => vec![0.; points.len()],
+        Interpolation::Spline => spline_second_derivatives(&points),
+    };
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return vec![f64::NAN; xs.len()];
+    };
+    let end_slope = |(x0, y0): (f64, f64), (x1, y1): (f64, f64)| {
+        if x1 > x0 {
+            (y1 - y0) / (x1 - x0)
+        } else {
+            0.
+        }
+    };
+    xs.iter()
+        .map(|&x| {
+            if x.is_nan() {
+                return f64::NAN;
+            }
+            if x < first.0 || x > last.0 {
+                let (end, slope) = if x < first.0 {
+                    (first, end_slope(first, *points.get(1).unwrap_or(&first)))
+                } else {
+                    let before = points.len().saturating_sub(2);
+                    (last, end_slope(points[before], last))
+                };
+                return match outside {
+                    OutsideRange::Nan => f64::NAN,
+                    OutsideRange::Nearest => end.1,
+                    OutsideRange::Extrapolate => end.1 + slope * (x - end.0),
+                };
+            }
+            let right = points.partition_point(|(px, _)| *px < x);
+            if right == 0 || points[right].0 == x {
+                return points[right].1;
+            }
+            let ((x0, y0), (x1, y1)) = (points[right - 1], points[right]);
+            let (m0, m1) = (second[right - 1], second[right]);
+            let h = x1 - x0;
+            let (a, b) = ((x1 - x) / h, (x - x0) / h);
+            a * y0 + b * y1 + ((a.powi(3) - a) * m0 + (b.powi(3) - b) * m1) * h * h / 6.
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spline_follows_cubic() {
+        let cubic = |x: f64| x.powi(3) - 0.5 * x;
+        let points = (0..=40)
+            .map(|i| -1. + i as f64 * 0.05)
+            .map(|x| (x, cubic(x)))
+            .collect_vec();
+        let xs = (0..=100).map(|i| -0.5 + i as f64 * 0.01).collect_vec();
+        let ys = resample(&points, &xs, Interpolation::Spline, OutsideRange::Nan);
+        for (x, y) in xs.iter().zip(ys) {
+            // the natural end conditions only disturb the ends of the range
+            assert!((y - cubic(*x)).abs() < 1e-4, "{x}: {y}");
+        }
+        // the spline passes through the points
+        let knots = points.iter().map(|(x, _)| *x).collect_vec();
+        let ys = resample(&points, &knots, Interpolation::Spline, OutsideRange::Nan);
+        for ((_, expected), y) in points.iter().zip(ys) {
+            assert!((y - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn spline_second_derivatives_solve_the_system() {
+        let points = [
+            (0., 1.),
+            (0.5, -2.),
+            (2., 0.5),
+            (2.25, 3.),
+            (4., -1.),
+            (7., 2.),
+        ];
+        let second = spline_second_derivatives(&points);
+        assert_eq!(second[0], 0.);
+        assert_eq!(second[points.len() - 1], 0.);
+        for i in 1..points.len() - 1 {
+            let (h0, h1) = (points[i].0 - points[i - 1].0, points[i + 1].0 - points[i].0);
+            let left = h0 * second[i - 1] + 2. * (h0 + h1) * second[i] + h1 * second[i + 1];
+            let right =
+                6. * ((points[i + 1].1 - points[i].1) / h1 - (points[i].1 - points[i - 1].1) / h0);
+            assert!((left - right).abs() < 1e-9, "row {i}: {left} != {right}");
+        }
+    }
+
+    #[test]
+    fn spline_of_line_is_exact() {
+        let points = [(0., 1.), (1., 3.), (3., 7.), (3.5, 8.)];
+        let xs = [0.25, 2., 3.2];
+        let ys = resample(&points, &xs, Interpolation::Spline, OutsideRange::Nan);
+        for (x, y) in xs.iter().zip(ys) {
+            assert!((y - (1. + 2. * x)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn outside_range_at_both_ends() {
+        let points = [(1., 2.), (2., 4.), (3., 5.)];
+        let xs = [0., 4.];
+        let resample = |outside| resample(&points, &xs, Interpolation::Linear, outside);
+        assert!(resample(OutsideRange::Nan).iter().all(|y| y.is_nan()));
+        assert_eq!(resample(OutsideRange::Nearest), vec![2., 5.]);
+        assert_eq!(resample(OutsideRange::Extrapolate), vec![0., 6.]);
+    }
+
+    #[test]
+    fn duplicate_xs_are_averaged() {
+        let points = [(1., 2.), (1., 4.), (2., 5.)];
+        let ys = resample(
+            &points,
+            &[1., 1.5],
+            Interpolation::Linear,
+            OutsideRange::Nan,
+        );
+        assert_eq!(ys, vec![3., 4.]);
+    }
+
+    #[test]
+    fn grid_count_and_step_span_all_plots() {
+        let plots: [&[(f64, f64)]; 2] = [&[(0., 0.), (0.5, 1.)], &[(1., 0.)]];
+        assert_eq!(
+            grid(&ResampleGrid::Count(5), &plots),
+            vec![0., 0.25, 0.5, 0.75, 1.]
+        );
+        assert_eq!(
+            grid(&ResampleGrid::Step(0.25), &plots),
+            vec![0., 0.25, 0.5, 0.75, 1.]
+        );
+        assert_eq!(grid(&ResampleGrid::Count(1), &plots), vec![0.]);
+        assert!(grid(&ResampleGrid::Step(0.), &plots).is_empty());
+        assert!(grid(&ResampleGrid::Step(-1.), &plots).is_empty());
+        assert!(grid(&ResampleGrid::Count(5), &[]).is_empty());
+        assert_eq!(
+            grid(&ResampleGrid::Explicit(vec![3., -1.]), &plots),
+            vec![3., -1.]
+        );
+    }
+
+    #[test]
+    fn grid_size_is_capped() {
+        let plots: [&[(f64, f64)]; 1] = [&[(0., 0.), (1., 1.)]];
+        let xs = grid(&ResampleGrid::Step(1e-9), &plots);
+        assert_eq!(xs.len(), MAX_GRID_POINTS);
+    }
+}
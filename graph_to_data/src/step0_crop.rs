@@ -1,18 +1,169 @@
-use super::unit_geometry::{UnitPoint, UnitQuadrilateral};
+use super::unit_geometry::{UnitInterval, UnitPoint, UnitQuadrilateral};
+use crate::{Error, PolarCalibration};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum CropMode {
+    /// Blend the edges of the quadrilateral linearly
+    #[default]
+    Bilinear,
+    /// Perspective rectification, e.g. for photos of printed plots
+    Homography,
+}
+
+#[derive(Clone, Debug)]
+enum CropKind {
+    Quadrilateral {
+        quadrilateral: UnitQuadrilateral,
+        homography: Option<[f32; 8]>,
+    },
+    /// Unwrapped into angle and radius
+    Polar {
+        polar: PolarCalibration,
+        dimensions: [u32; 2],
+    },
+}
+
+/// Maps pixels of the cropped image to positions within the uncropped image
+#[derive(Clone, Debug)]
+pub struct Crop {
+    kind: CropKind,
+    steps_x: u32,
+    steps_y: u32,
+}
+impl Crop {
+    pub fn new(
+        quadrilateral: UnitQuadrilateral,
+        mode: CropMode,
+        steps_x: u32,
+        steps_y: u32,
+    ) -> Result<Self, Error> {
+        check_steps(steps_x, steps_y)?;
+        quadrilateral.check()?;
+        let homography = match mode {
+            CropMode::Bilinear => None,
+            CropMode::Homography => {
+                if !quadrilateral.is_convex() {
+                    return Err(Error::QuadrilateralNotConvex);
+                }
+                Some(homography(&quadrilateral))
+            }
+        };
+        Ok(Self {
+            kind: CropKind::Quadrilateral {
+                quadrilateral,
+                homography,
+            },
+            steps_x,
+            steps_y,
+        })
+    }
+
+    /// The dimensions of the uncropped image are needed as the circles are round in pixels
+    pub fn polar(
+        polar: &PolarCalibration,
+        dimensions: [u32; 2],
+        steps_x: u32,
+        steps_y: u32,
+    ) -> Result<Self, Error> {
+        check_steps(steps_x, steps_y)?;
+        polar.check(dimensions)?;
+        Ok(Self {
+            kind: CropKind::Polar {
+                polar: polar.clone(),
+                dimensions,
+            },
+            steps_x,
+            steps_y,
+        })
+    }
+
+    pub fn steps_x(&self) -> u32 {
+        self.steps_x
+    }
+
+    pub fn steps_y(&self) -> u32 {
+        self.steps_y
+    }
+
+    /// Position within the uncropped image (in unit coordinates) of a pixel of the cropped image
+    pub fn source_position(&self, x: f32, y: f32) -> [f32; 2] {
+        let u = x / (self.steps_x - 1) as f32;
+        let v = y / (self.steps_y - 1) as f32;
+        match &self.kind {
+            CropKind::Quadrilateral {
+                homography: Some([a, b, c, d, e, f, g, h]),
+                ..
+            } => {
+                let w = g * u + h * v + 1.;
+                [(a * u + b * v + c) / w, (d * u + e * v + f) / w]
+            }
+            CropKind::Quadrilateral { quadrilateral, .. } => {
+                let UnitQuadrilateral { lt, lb, rt, rb } = quadrilateral;
+                let lerp = |a: f32, b: f32, t: f32| b * t + (1. - t) * a;
+                let l = [lerp(lt.x.0, lb.x.0, v), lerp(lt.y.0, lb.y.0, v)];
+                let r = [lerp(rt.x.0, rb.x.0, v), lerp(rt.y.0, rb.y.0, v)];
+                [lerp(l[0], r[0], u), lerp(l[1], r[1], u)]
+            }
+            CropKind::Polar { polar, dimensions } => {
+                polar.source_position(x, y, [self.steps_x, self.steps_y], *dimensions)
+            }
+        }
+    }
+}
+
+/// The first and last pixel of each direction are mapped onto the edges, hence two are needed
+fn check_steps(steps_x: u32, steps_y: u32) -> Result<(), Error> {
+    if steps_x < 2 || steps_y < 2 {
+        return Err(Error::StepSettingsInvalid { steps_x, steps_y });
+    }
+    Ok(())
+}
+
+/// Projective mapping of the unit square onto the quadrilateral, see
+/// Heckbert, "Fundamentals of Texture Mapping and Image Warping", 1989
+fn homography(quadrilateral: &UnitQuadrilateral) -> [f32; 8] {
+    let UnitQuadrilateral { lt, lb, rt, rb } = quadrilateral;
+    let [x0, x1, x2, x3] = [lt.x.0, rt.x.0, rb.x.0, lb.x.0];
+    let [y0, y1, y2, y3] = [lt.y.0, rt.y.0, rb.y.0, lb.y.0];
+    let sx = x0 - x1 + x2 - x3;
+    let sy = y0 - y1 + y2 - y3;
+    let (g, h) = if sx == 0. && sy == 0. {
+        (0., 0.)
+    } else {
+        let dx1 = x1 - x2;
+        let dx2 = x3 - x2;
+        let dy1 = y1 - y2;
+        let dy2 = y3 - y2;
+        let determinant = dx1 * dy2 - dx2 * dy1;
+        (
+            (sx * dy2 - dx2 * sy) / determinant,
+            (dx1 * sy - sx * dy1) / determinant,
+        )
+    };
+    [
+        x1 - x0 + g * x1,
+        x3 - x0 + h * x3,
+        x0,
+        y1 - y0 + g * y1,
+        y3 - y0 + h * y3,
+        y0,
+        g,
+        h,
+    ]
+}
 
 pub trait ImageInterpolate<Pixel: image::Pixel> {
     fn interpolate_pixel(&self, point: UnitPoint) -> Pixel;
     fn crop(
         &self,
-        quadrilateral: UnitQuadrilateral,
-        steps_x: u32,
-        steps_y: u32,
+        crop: &Crop,
     ) -> image::ImageBuffer<Pixel, Vec<<Pixel as image::Pixel>::Subpixel>> {
-        let UnitQuadrilateral { lt, lb, rt, rb } = quadrilateral;
-        image::ImageBuffer::from_fn(steps_x, steps_y, |x, y| {
-            let l = UnitPoint::interpolate(lt, lb, steps_y, y);
-            let r = UnitPoint::interpolate(rt, rb, steps_y, y);
-            let target = UnitPoint::interpolate(l, r, steps_x, x);
+        image::ImageBuffer::from_fn(crop.steps_x, crop.steps_y, |x, y| {
+            let [x, y] = crop.source_position(x as f32, y as f32);
+            let target = UnitPoint {
+                x: UnitInterval(x.clamp(0., 1.)),
+                y: UnitInterval(y.clamp(0., 1.)),
+            };
             self.interpolate_pixel(target)
         })
     }
@@ -44,3 +195,100 @@ where
         imageproc::pixelops::interpolate(l, r, 1. - x_fraction)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quadrilateral(corners: [[f32; 2]; 4]) -> UnitQuadrilateral {
+        let [lt, rt, rb, lb] = corners.map(|p| UnitPoint::new(p).unwrap());
+        UnitQuadrilateral { lt, lb, rt, rb }
+    }
+
+    /// A photographed plot, i.e. a perspective distortion of a rectangle
+    const PERSPECTIVE: [[f32; 2]; 4] = [[0.2, 0.1], [0.85, 0.2], [0.9, 0.8], [0.1, 0.95]];
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (actual[0] - expected[0]).abs() < 1e-5 && (actual[1] - expected[1]).abs() < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn homography_maps_corners_exactly() {
+        let crop = Crop::new(quadrilateral(PERSPECTIVE), CropMode::Homography, 101, 201).unwrap();
+        let [lt, rt, rb, lb] = PERSPECTIVE;
+        assert_close(crop.source_position(0., 0.), lt);
+        assert_close(crop.source_position(100., 0.), rt);
+        assert_close(crop.source_position(100., 200.), rb);
+        assert_close(crop.source_position(0., 200.), lb);
+    }
+
+    #[test]
+    fn homography_keeps_lines_straight() {
+        let crop = Crop::new(quadrilateral(PERSPECTIVE), CropMode::Homography, 101, 101).unwrap();
+        // the center of the square is the intersection of the diagonals
+        let [lt, rt, rb, lb] = PERSPECTIVE;
+        let center = crop.source_position(50., 50.);
+        let orientation = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| {
+            (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+        };
+        assert!(orientation(lt, rb, center).abs() < 1e-5);
+        assert!(orientation(rt, lb, center).abs() < 1e-5);
+        // and each row of pixels is a straight line
+        let row = [0., 30., 70., 100.].map(|x| crop.source_position(x, 20.));
+        assert!(orientation(row[0], row[3], row[1]).abs() < 1e-5);
+        assert!(orientation(row[0], row[3], row[2]).abs() < 1e-5);
+    }
+
+    #[test]
+    fn homography_of_rectangle_is_bilinear() {
+        let rectangle = [[0.2, 0.1], [0.8, 0.1], [0.8, 0.7], [0.2, 0.7]];
+        let homography = Crop::new(quadrilateral(rectangle), CropMode::Homography, 11, 11).unwrap();
+        let bilinear = Crop::new(quadrilateral(rectangle), CropMode::Bilinear, 11, 11).unwrap();
+        for (x, y) in [(0., 0.), (3., 7.), (10., 5.)] {
+            assert_close(
+                homography.source_position(x, y),
+                bilinear.source_position(x, y),
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_steps() {
+        for (steps_x, steps_y) in [(0, 100), (1, 100), (100, 0), (100, 1)] {
+            assert!(matches!(
+                Crop::new(
+                    UnitQuadrilateral::unit_square(),
+                    CropMode::Bilinear,
+                    steps_x,
+                    steps_y
+                ),
+                Err(Error::StepSettingsInvalid { .. })
+            ));
+        }
+        assert!(Crop::new(UnitQuadrilateral::unit_square(), CropMode::Bilinear, 2, 2).is_ok());
+    }
+
+    #[test]
+    fn invalid_quadrilaterals() {
+        let concave = [[0.1, 0.1], [0.9, 0.1], [0.5, 0.3], [0.1, 0.9]];
+        assert!(matches!(
+            Crop::new(quadrilateral(concave), CropMode::Homography, 101, 101),
+            Err(Error::QuadrilateralNotConvex)
+        ));
+        // bilinear blending does not need convexity
+        assert!(Crop::new(quadrilateral(concave), CropMode::Bilinear, 101, 101).is_ok());
+        let collapsed = [[0.1, 0.1], [0.5, 0.1], [0.9, 0.1], [0.1, 0.9]];
+        assert!(matches!(
+            Crop::new(quadrilateral(collapsed), CropMode::Homography, 101, 101),
+            Err(Error::QuadrilateralDegenerate)
+        ));
+        let crossing = [[0.1, 0.1], [0.9, 0.9], [0.9, 0.1], [0.1, 0.9]];
+        assert!(matches!(
+            Crop::new(quadrilateral(crossing), CropMode::Bilinear, 101, 101),
+            Err(Error::QuadrilateralSelfIntersecting)
+        ));
+    }
+}
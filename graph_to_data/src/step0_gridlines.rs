@@ -0,0 +1,180 @@
+use itertools::Itertools;
+
+use crate::{ColorMetric, PaletteColor};
+
+/// Background pixels between the dashes of dashed gridlines, curves which cross a line are not gaps
+const MAX_GAP: usize = 10;
+
+/// Long straight horizontal and vertical lines, e.g. gridlines and the axis frame, are replaced by
+/// the background
+/// Curves which cross a line have another color, hence the crossing pixels are kept. Lines in one
+/// of the colors of the curves are kept, e.g. flat parts of curves.
+pub fn remove_gridlines(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    colors: &[PaletteColor],
+    settings: &crate::Settings,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let background = settings.background();
+    let color_radius = settings.step1_step2_color_radius as f32;
+    let metric = settings.step1_step2_color_metric;
+    let min_width = (settings.step0_gridline_min_length_fraction * width as f32).max(2.) as usize;
+    let min_height = (settings.step0_gridline_min_length_fraction * height as f32).max(2.) as usize;
+    let is_curve_color = |color: &image::Rgba<u8>| {
+        colors
+            .iter()
+            .any(|palette| metric.distance(&palette.color, color) < color_radius)
+    };
+
+    let mut removed = Vec::new();
+    for y in 0..height {
+        let row = (0..width).map(|x| image.get_pixel(x, y));
+        for x in line_pixels(
+            row,
+            &background,
+            color_radius,
+            metric,
+            min_width,
+            is_curve_color,
+        ) {
+            removed.push((x as u32, y));
+        }
+    }
+    for x in 0..width {
+        let column = (0..height).map(|y| image.get_pixel(x, y));
+        for y in line_pixels(
+            column,
+            &background,
+            color_radius,
+            metric,
+            min_height,
+            is_curve_color,
+        ) {
+            removed.push((x, y as u32));
+        }
+    }
+    let mut cleaned = image.clone();
+    for (x, y) in removed {
+        cleaned.put_pixel(x, y, background);
+    }
+    cleaned
+}
+
+/// Positions of pixels along a row or column which belong to a line
+/// Anti-aliased pixels of a line are blends of its color with the background, the colors are tried
+/// from the most frequent one, e.g. a flat part of a curve can be more frequent than a gridline.
+/// Lines with a color of a curve are not returned.
+fn line_pixels<'a>(
+    pixels: impl Iterator<Item = &'a image::Rgba<u8>>,
+    background: &image::Rgba<u8>,
+    color_radius: f32,
+    metric: ColorMetric,
+    min_length: usize,
+    is_curve_color: impl Fn(&image::Rgba<u8>) -> bool,
+) -> Vec<usize> {
+    let mut background_count = vec![0];
+    let mut positions_per_color = std::collections::HashMap::<_, Vec<_>>::new();
+    for (position, c) in pixels.enumerate() {
+        let is_background = metric.distance(background, c) < color_radius;
+        background_count.push(background_count.last().unwrap() + is_background as usize);
+        if !is_background {
+            positions_per_color.entry(*c).or_default().push(position);
+        }
+    }
+    let gap = |start: usize, end: usize| background_count[end] - background_count[start + 1];
+    // most frequent first, a color belongs to the line of the first color it is a blend of
+    let mut remaining = positions_per_color
+        .into_iter()
+        .sorted_by_key(|(c, positions)| std::cmp::Reverse((positions.len(), c.0)))
+        .map(Some)
+        .collect_vec();
+    let background_rgb = [0, 1, 2].map(|i| background.0[i] as f32);
+    let mut line = Vec::new();
+    for index in 0..remaining.len() {
+        let Some((color, _)) = remaining[index] else {
+            continue;
+        };
+        let direction = [0, 1, 2].map(|i| color.0[i] as f32 - background_rgb[i]);
+        let length_squared = direction.iter().map(|d| d * d).sum::<f32>();
+        let is_blend = |c: &image::Rgba<u8>| {
+            if *c == color {
+                return true;
+            }
+            let alpha = (0..3)
+                .map(|i| (c.0[i] as f32 - background_rgb[i]) * direction[i])
+                .sum::<f32>()
+                / length_squared;
+            let [r, g, b] = [0, 1, 2].map(|i| {
+                (background_rgb[i] + alpha * direction[i])
+                    .round()
+                    .clamp(0., 255.) as u8
+            });
+            alpha > 0. && metric.distance(&image::Rgba([r, g, b, 255]), c) <= color_radius
+        };
+        let mut positions = Vec::new();
+        for blend in &mut remaining[index..] {
+            if blend.as_ref().is_some_and(|(c, _)| is_blend(c)) {
+                positions.extend(blend.take().unwrap().1);
+            }
+        }
+        if is_curve_color(&color) {
+            continue;
+        }
+        positions.sort_unstable();
+
+        let mut start = 0;
+        for end in 1..=positions.len() {
+            if end < positions.len() && gap(positions[end - 1], positions[end]) <= MAX_GAP {
+                continue;
+            }
+            if positions[end - 1] - positions[start] + 1 >= min_length {
+                line.extend_from_slice(&positions[start..end]);
+            }
+            start = end;
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRID: image::Rgba<u8> = image::Rgba([200, 200, 200, 255]);
+    const CURVE: image::Rgba<u8> = image::Rgba([0, 69, 134, 255]);
+
+    /// Gridlines every 20 pixels and a horizontal curve in between
+    fn grid_with_curve() -> image::RgbaImage {
+        image::RgbaImage::from_fn(100, 60, |x, y| {
+            if y == 30 {
+                CURVE
+            } else if x % 20 == 10 || y % 20 == 10 {
+                GRID
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn removes_gridlines_but_not_curves() {
+        let image = grid_with_curve();
+        let colors = [PaletteColor {
+            color: CURVE,
+            pixel_count: 100,
+        }];
+        let cleaned = remove_gridlines(&image, &colors, &crate::Settings::default());
+        assert!(cleaned.pixels().all(|c| *c != GRID));
+        for x in 0..100 {
+            assert_eq!(cleaned[(x, 30)], CURVE);
+        }
+    }
+
+    #[test]
+    fn curves_without_palette_color_are_lines() {
+        let cleaned = remove_gridlines(&grid_with_curve(), &[], &crate::Settings::default());
+        assert!(cleaned
+            .pixels()
+            .all(|c| *c == image::Rgba([255, 255, 255, 255])));
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::ColorMetric;
+
+/// Upper limit of boxes of the median cut
+const MAX_CLUSTERS: usize = 64;
+const KMEANS_ITERATIONS: usize = 10;
+
+type Entry = ([f32; 3], f32);
+
+fn to_color(rgb: [f32; 3]) -> image::Rgba<u8> {
+    let [r, g, b] = rgb.map(|c| c.round().clamp(0., 255.) as u8);
+    image::Rgba([r, g, b, 255])
+}
+
+fn extent(entries: &[Entry]) -> ([f32; 3], [f32; 3]) {
+    entries
+        .iter()
+        .fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), (rgb, _)| {
+            (
+                [0, 1, 2].map(|i| min[i].min(rgb[i])),
+                [0, 1, 2].map(|i| max[i].max(rgb[i])),
+            )
+        })
+}
+
+fn mean(entries: &[Entry]) -> Entry {
+    let count = entries.iter().map(|(_, count)| count).sum::<f32>();
+    let rgb = [0, 1, 2].map(|i| {
+        entries
+            .iter()
+            .map(|(rgb, count)| rgb[i] * count)
+            .sum::<f32>()
+            / count
+    });
+    (rgb, count)
+}
+
+/// Clusters the histogram of the non-background pixels and returns the centroids,
+/// sorted by descending pixel count
+/// The number of clusters follows from the color radius: the median cut splits until all boxes
+/// are smaller than the radius, after a k-means refinement centroids within the radius are merged.
+pub fn cluster(
+    histogram: HashMap<image::Rgba<u8>, u32>,
+    background: &image::Rgba<u8>,
+    color_radius: f32,
+    metric: ColorMetric,
+) -> Vec<image::Rgba<u8>> {
+    // sorted, so that the result does not depend on the order of the hash map
+    let entries = histogram
+        .into_iter()
+        .sorted_by_key(|(c, _)| c.0)
+        .map(|(c, count)| ([c[0], c[1], c[2]].map(|c| c as f32), count as f32))
+        .collect_vec();
+    if entries.is_empty() {
+        return Vec::new();
+    }
+
+    // median cut
+    let mut boxes = vec![entries];
+    while boxes.len() < MAX_CLUSTERS {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(|(index, entries)| {
+                let (min, max) = extent(entries);
+                (index, metric.distance(&to_color(min), &to_color(max)))
+            })
+            .filter(|(_, distance)| *distance > color_radius)
+            .max_by(|(_, left), (_, right)| left.total_cmp(right));
+        let Some((index, _)) = widest else {
+            break;
+        };
+        let mut entries = boxes.swap_remove(index);
+        let (min, max) = extent(&entries);
+        let channel = (0..3)
+            .max_by(|&l, &r| (max[l] - min[l]).total_cmp(&(max[r] - min[r])))
+            .unwrap();
+        entries.sort_by(|l, r| l.0[channel].total_cmp(&r.0[channel]));
+        let half = entries.iter().map(|(_, count)| count).sum::<f32>() / 2.;
+        let mut sum = 0.;
+        let median = entries
+            .iter()
+            .position(|(_, count)| {
+                sum += count;
+                sum >= half
+            })
+            .unwrap_or(0);
+        let upper = entries.split_off((median + 1).clamp(1, entries.len() - 1));
+        boxes.push(entries);
+        boxes.push(upper);
+    }
+
+    // k-means
+    let mut centroids = boxes.iter().map(|entries| mean(entries).0).collect_vec();
+    let entries = boxes.concat();
+    let mut clusters = Vec::new();
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut members = vec![Vec::new(); centroids.len()];
+        for entry in &entries {
+            let nearest = centroids
+                .iter()
+                .map(|centroid| {
+                    (0..3)
+                        .map(|i| (centroid[i] - entry.0[i]).powi(2))
+                        .sum::<f32>()
+                })
+                .enumerate()
+                .min_by(|(_, left), (_, right)| left.total_cmp(right))
+                .map(|(index, _)| index)
+                .unwrap();
+            members[nearest].push(*entry);
+        }
+        clusters = members
+            .iter()
+            .filter(|members| !members.is_empty())
+            .map(|members| mean(members))
+            .collect_vec();
+        centroids = clusters.iter().map(|(rgb, _)| *rgb).collect();
+    }
+
+    // merge centroids within the color radius
+    while let Some((i, j)) = (0..clusters.len()).tuple_combinations().find(|&(i, j)| {
+        metric.distance(&to_color(clusters[i].0), &to_color(clusters[j].0)) <= color_radius
+    }) {
+        let other = clusters.swap_remove(j);
+        clusters[i] = mean(&[clusters[i], other]);
+    }
+
+    // anti-aliased edges are blends of a larger cluster and the background
+    let background = [0, 1, 2].map(|i| background.0[i] as f32);
+    let is_blend = |(rgb, _): &Entry, (other, _): &Entry| {
+        let direction = [0, 1, 2].map(|i| other[i] - background[i]);
+        let length_squared = direction.iter().map(|d| d * d).sum::<f32>();
+        if length_squared == 0. {
+            return false;
+        }
+        let alpha = (0..3)
+            .map(|i| (rgb[i] - background[i]) * direction[i])
+            .sum::<f32>()
+            / length_squared;
+        let blend = [0, 1, 2].map(|i| background[i] + alpha * direction[i]);
+        alpha > 0.
+            && alpha < 1.
+            && metric.distance(&to_color(*rgb), &to_color(blend)) <= color_radius
+    };
+    // the most saturated clusters first, blends can have more pixels than the pure color
+    let mut kept: Vec<Entry> = Vec::new();
+    for cluster in clusters.into_iter().sorted_by(|(left, _), (right, _)| {
+        let distance = |rgb: &[f32; 3]| {
+            (0..3)
+                .map(|i| (rgb[i] - background[i]).powi(2))
+                .sum::<f32>()
+        };
+        distance(right).total_cmp(&distance(left))
+    }) {
+        // the pixels of a blend belong to the color it blends into
+        if let Some(other) = kept.iter_mut().find(|other| is_blend(&cluster, other)) {
+            other.1 += cluster.1;
+        } else {
+            kept.push(cluster);
+        }
+    }
+    kept.into_iter()
+        .sorted_by(|(_, left), (_, right)| right.total_cmp(left))
+        .map(|(rgb, _)| to_color(rgb))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+    fn histogram(counts: &[([u8; 3], u32)]) -> HashMap<image::Rgba<u8>, u32> {
+        counts
+            .iter()
+            .map(|&([r, g, b], count)| (image::Rgba([r, g, b, 255]), count))
+            .collect()
+    }
+
+    #[test]
+    fn close_colors_and_blends_are_merged() {
+        let histogram = histogram(&[
+            ([200, 0, 0], 100),
+            ([202, 2, 0], 20),
+            // anti-aliased edge, halfway between red and the background
+            ([227, 127, 127], 30),
+            ([0, 0, 200], 50),
+        ]);
+        for metric in [ColorMetric::Rgb, ColorMetric::Cielab] {
+            assert_eq!(
+                cluster(histogram.clone(), &WHITE, 5., metric),
+                [image::Rgba([200, 0, 0, 255]), image::Rgba([0, 0, 200, 255])],
+                "{metric:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn clusters_are_sorted_by_pixel_count() {
+        let histogram = histogram(&[([0, 0, 200], 10), ([0, 150, 0], 30), ([200, 0, 0], 20)]);
+        assert_eq!(
+            cluster(histogram, &WHITE, 5., ColorMetric::Rgb),
+            [[0, 150, 0, 255], [200, 0, 0, 255], [0, 0, 200, 255]].map(image::Rgba)
+        );
+        assert!(cluster(HashMap::new(), &WHITE, 5., ColorMetric::Rgb).is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{color_distance, color_distance_three};
+use crate::{ColorMetric, PaletteColor, PaletteMethod};
 
 #[derive(Debug, Default)]
 struct ColorExtractor {
@@ -9,52 +9,180 @@ struct ColorExtractor {
 pub fn extract_colors(
     image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
     settings: &crate::Settings,
-) -> Vec<image::Rgba<u8>> {
-    let color_extractor = ColorExtractor::classify_image(
-        image,
-        settings.step1_step2_color_radius,
-        settings.step1_ignore_gray,
-    );
+) -> Vec<PaletteColor> {
+    let background = settings.background();
+    let color_extractor = match settings.step1_palette {
+        PaletteMethod::FirstSeen => ColorExtractor::classify_image(
+            image,
+            &background,
+            settings.step1_step2_color_radius,
+            settings.step1_step2_color_metric,
+            settings.step1_ignore_gray,
+            settings.step1_monochrome,
+        ),
+        PaletteMethod::Clustering => ColorExtractor::cluster_image(
+            image,
+            &background,
+            settings.step1_step2_color_radius,
+            settings.step1_step2_color_metric,
+            settings.step1_ignore_gray,
+            settings.step1_monochrome,
+        ),
+    };
     color_extractor.extract(
         image,
-        settings.step1_width_minimial_fraction,
-        settings.step1_height_maximal_fraction,
+        settings.width_minimal_fraction(),
+        settings.color_height_maximal_fraction(),
+        settings.max_dash_gap(),
     )
 }
+
+/// Number of covered columns, where gaps up to `max_gap` between them count as covered,
+/// e.g. the gaps between the dashes of a dashed curve
+pub fn covered_width(covered: impl Iterator<Item = bool>, max_gap: usize) -> usize {
+    let mut count = 0;
+    let mut gap = None;
+    for covered in covered {
+        match (covered, gap) {
+            (true, Some(length)) if length <= max_gap => count += length + 1,
+            (true, _) => count += 1,
+            (false, Some(length)) => gap = Some(length + 1),
+            (false, None) => {}
+        }
+        if covered {
+            gap = Some(0);
+        }
+    }
+    count
+}
+/// Gray pixels of monochrome plots which are close to the background are mostly anti-aliased edges
+const MONOCHROME_MIN_CONTRAST: u8 = 64;
+
+fn lightness(c: &image::Rgba<u8>) -> u8 {
+    let mut mean = [c.0[0], c.0[1], c.0[2]];
+    mean.sort();
+    mean[1]
+}
+
+/// The most frequent color of the image
+pub fn detect_background(image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>) -> [u8; 3] {
+    let mut histogram = std::collections::HashMap::new();
+    for c in image.pixels() {
+        *histogram.entry([c.0[0], c.0[1], c.0[2]]).or_insert(0u32) += 1;
+    }
+    histogram
+        .into_iter()
+        .max_by_key(|(c, count)| (*count, *c))
+        .map(|(c, _)| c)
+        .unwrap_or([255, 255, 255])
+}
+
+/// The background, the axes and text in the contrasting color (black on a light background,
+/// white on a dark one) and (optionally) gray pixels are not considered as curve colors,
+/// in monochrome plots only gray levels which differ from the background are curve colors
+pub(crate) fn is_background(
+    c: &image::Rgba<u8>,
+    background: &image::Rgba<u8>,
+    color_radius: f32,
+    metric: ColorMetric,
+    ignore_gray: bool,
+    monochrome: bool,
+) -> bool {
+    let mean = lightness(c);
+    let is_background = metric.distance(background, c) < color_radius;
+    let is_gray = metric.distance_to_rgb(&image::Rgb([mean, mean, mean]), c) < color_radius;
+    if monochrome {
+        return is_background
+            || !is_gray
+            || mean.abs_diff(lightness(background)) < MONOCHROME_MIN_CONTRAST;
+    }
+    let contrast = if lightness(background) > 127 { 0 } else { 255 };
+    is_background
+        || metric.distance_to_rgb(&image::Rgb([contrast; 3]), c) < color_radius
+        || (ignore_gray && is_gray)
+}
+
 impl ColorExtractor {
     fn classify_image(
         image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        background: &image::Rgba<u8>,
         color_radius: u8,
+        metric: ColorMetric,
         ignore_gray: bool,
+        monochrome: bool,
     ) -> Self {
+        let color_radius = color_radius as f32;
         let mut colors: Vec<image::Rgba<u8>> = Vec::new();
         let mut color_occurences = Vec::new();
+        // colors are only appended, hence equal pixels are always assigned to the same color
+        let mut known = std::collections::HashMap::new();
         for x in 0..image.width() {
             for y in 0..image.height() {
                 let c = image.get_pixel(x, y);
-                let mut mean = c.0;
-                mean.sort();
-                let mean = mean[1];
-                if color_distance_three(&image::Rgb([255, 255, 255]), c) < color_radius
-                    || color_distance_three(&image::Rgb([0, 0, 0]), c) < color_radius
-                    || (ignore_gray
-                        && color_distance_three(&image::Rgb([mean, mean, mean]), c) < color_radius)
-                {
-                    continue;
-                }
-                let color_occurences = {
-                    if let Some(color_index) = colors
-                        .iter()
-                        .position(|cc| color_distance(cc, c) <= color_radius)
-                    {
-                        color_occurences.get_mut(color_index).unwrap()
-                    } else {
-                        colors.push(*c);
-                        color_occurences.push(vec![0u32; image.width() as _]);
-                        color_occurences.last_mut().unwrap()
+                let color_index = *known.entry(*c).or_insert_with(|| {
+                    if is_background(c, background, color_radius, metric, ignore_gray, monochrome) {
+                        return None;
                     }
-                };
-                color_occurences[x as usize] += 1;
+                    Some(
+                        colors
+                            .iter()
+                            .position(|cc| metric.distance(cc, c) <= color_radius)
+                            .unwrap_or_else(|| {
+                                colors.push(*c);
+                                color_occurences.push(vec![0u32; image.width() as _]);
+                                colors.len() - 1
+                            }),
+                    )
+                });
+                if let Some(color_index) = color_index {
+                    color_occurences[color_index][x as usize] += 1;
+                }
+            }
+        }
+        Self {
+            colors,
+            color_occurences,
+        }
+    }
+
+    /// Curve colors are the centroids of clusters of the color histogram,
+    /// each pixel is assigned to the closest centroid within the color radius
+    fn cluster_image(
+        image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        background: &image::Rgba<u8>,
+        color_radius: u8,
+        metric: ColorMetric,
+        ignore_gray: bool,
+        monochrome: bool,
+    ) -> Self {
+        let color_radius = color_radius as f32;
+        let mut histogram = std::collections::HashMap::new();
+        for c in image.pixels() {
+            if let Some(count) = histogram.get_mut(c) {
+                *count += 1;
+            } else if !is_background(c, background, color_radius, metric, ignore_gray, monochrome) {
+                histogram.insert(*c, 1u32);
+            }
+        }
+        let colors = crate::step1_clustering::cluster(histogram, background, color_radius, metric);
+
+        let mut color_occurences = vec![vec![0u32; image.width() as _]; colors.len()];
+        let mut known = std::collections::HashMap::new();
+        for (x, _, c) in image.enumerate_pixels() {
+            let color_index = *known.entry(*c).or_insert_with(|| {
+                if is_background(c, background, color_radius, metric, ignore_gray, monochrome) {
+                    return None;
+                }
+                colors
+                    .iter()
+                    .map(|cc| metric.distance(cc, c))
+                    .enumerate()
+                    .filter(|(_, distance)| *distance <= color_radius)
+                    .min_by(|(_, left), (_, right)| left.total_cmp(right))
+                    .map(|(color_index, _)| color_index)
+            });
+            if let Some(color_index) = color_index {
+                color_occurences[color_index][x as usize] += 1;
             }
         }
         Self {
@@ -68,21 +196,48 @@ impl ColorExtractor {
         image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
         width_minimial_fraction: f32,
         height_maximal_fraction: f32,
-    ) -> Vec<image::Rgba<u8>> {
+        max_gap: usize,
+    ) -> Vec<PaletteColor> {
         let Self {
-            mut colors,
+            colors,
             color_occurences,
         } = self;
 
-        for (color_index, color_occurence) in color_occurences.into_iter().enumerate().rev() {
-            let max = color_occurence.iter().cloned().max().unwrap();
-            let count = color_occurence.into_iter().filter(|x| x > &0).count();
-            if (count as f32 / image.width() as f32) < width_minimial_fraction
-                || (max as f32 / image.height() as f32) > height_maximal_fraction
-            {
-                colors.remove(color_index);
-            }
-        }
         colors
+            .into_iter()
+            .zip(color_occurences)
+            .filter_map(|(color, color_occurence)| {
+                let max = color_occurence.iter().cloned().max().unwrap();
+                let count = covered_width(color_occurence.iter().map(|x| x > &0), max_gap);
+                if (count as f32 / image.width() as f32) < width_minimial_fraction
+                    || (max as f32 / image.height() as f32) > height_maximal_fraction
+                {
+                    return None;
+                }
+                Some(PaletteColor {
+                    color,
+                    pixel_count: color_occurence.into_iter().sum(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn covered(pattern: &str, max_gap: usize) -> usize {
+        covered_width(pattern.chars().map(|c| c == '#'), max_gap)
+    }
+
+    #[test]
+    fn short_gaps_count_as_covered() {
+        assert_eq!(covered("##..#", 2), 5);
+        assert_eq!(covered("#...#", 2), 2);
+        assert_eq!(covered("#...#", 0), 2);
+        // gaps at the ends are not between dashes
+        assert_eq!(covered("..##..", 2), 2);
+        assert_eq!(covered("", 2), 0);
     }
 }
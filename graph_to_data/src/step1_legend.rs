@@ -0,0 +1,367 @@
+use itertools::Itertools;
+
+use crate::{step2_color_filtering, Legend, LegendEntry, PaletteColor, MISSED};
+
+/// Swatches are at least this wide, thinner components are parts of text or curves
+const MIN_SWATCH_WIDTH: u32 = 3;
+/// Swatches of one legend start at the same column, up to anti-aliasing
+const ALIGNMENT_TOLERANCE: u32 = 2;
+/// Labels are text in a neutral color, colored pixels right of a swatch are usually the next dash
+const MAX_TEXT_SATURATION: u8 = 48;
+/// Background between the entries and the legend frame
+const LEGEND_PADDING: u32 = 3;
+/// A row or column of the legend frame is mostly covered
+const MIN_FRAME_COVERAGE: f32 = 0.8;
+
+struct Candidate {
+    color: image::Rgba<u8>,
+    swatch: [u32; 4],
+    label: [u32; 4],
+}
+
+/// Legend entries are short swatches of the curve colors with a label to their right, stacked
+/// vertically at the same column
+/// The label must look like text, otherwise a dash of a curve followed by the next dash would be
+/// an entry. Text of monochrome plots has the color of the curves, hence at least two aligned
+/// entries are required instead.
+pub fn detect_legend(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    colors: &[PaletteColor],
+    settings: &crate::Settings,
+) -> Option<Legend> {
+    let (width, height) = image.dimensions();
+    let max_symbol_size = settings.step2_max_symbol_size.max(MIN_SWATCH_WIDTH);
+    let max_swatch_width = (2 * max_symbol_size).max(width / 8);
+
+    let mut candidates = Vec::new();
+    for palette_color in colors {
+        let mask = step2_color_filtering::color_filtering(image, &palette_color.color, settings);
+        let labels = imageproc::region_labelling::connected_components(
+            &mask,
+            imageproc::region_labelling::Connectivity::Eight,
+            MISSED,
+        );
+        let mut boxes: Vec<[u32; 4]> = Vec::new();
+        for (x, y, label) in labels.enumerate_pixels() {
+            let label = label.0[0] as usize;
+            if label == 0 {
+                continue;
+            }
+            if boxes.len() < label {
+                boxes.resize(label, [u32::MAX, u32::MAX, 0, 0]);
+            }
+            let [left, top, right, bottom] = &mut boxes[label - 1];
+            *left = (*left).min(x);
+            *top = (*top).min(y);
+            *right = (*right).max(x);
+            *bottom = (*bottom).max(y);
+        }
+        for swatch @ [left, top, right, bottom] in boxes {
+            let (swatch_width, swatch_height) = (right - left + 1, bottom - top + 1);
+            if swatch_width < MIN_SWATCH_WIDTH
+                || swatch_width > max_swatch_width
+                || swatch_height > max_symbol_size
+            {
+                continue;
+            }
+            if let Some(label) = label_rect(image, swatch, max_symbol_size, settings)
+                .filter(|label| settings.step1_monochrome || is_text(image, *label, settings))
+            {
+                candidates.push(Candidate {
+                    color: palette_color.color,
+                    swatch,
+                    label,
+                });
+            }
+        }
+    }
+
+    let min_entries = if settings.step1_monochrome { 2 } else { 1 };
+    let entries = aligned(candidates)
+        .into_iter()
+        .flat_map(|group| stacks(group, max_symbol_size))
+        .map(|stack| {
+            stack
+                .into_iter()
+                .unique_by(|candidate| candidate.color)
+                .collect_vec()
+        })
+        .filter(|stack| stack.len() >= min_entries)
+        .max_by_key(|stack| stack.len())?;
+
+    let rect = entries
+        .iter()
+        .fold([u32::MAX, u32::MAX, 0, 0], |rect, entry| {
+            let [left, top, right, bottom] = rect;
+            [
+                left.min(entry.swatch[0]).min(entry.label[0]),
+                top.min(entry.swatch[1]).min(entry.label[1]),
+                right.max(entry.swatch[2]).max(entry.label[2]),
+                bottom.max(entry.swatch[3]).max(entry.label[3]),
+            ]
+        });
+    let [left, top, right, bottom] = rect;
+    let rect = [
+        left.saturating_sub(LEGEND_PADDING),
+        top.saturating_sub(LEGEND_PADDING),
+        (right + LEGEND_PADDING).min(width - 1),
+        (bottom + LEGEND_PADDING).min(height - 1),
+    ];
+    let rect = include_frame(image, rect, max_symbol_size, settings);
+    Some(Legend {
+        rect,
+        entries: entries
+            .into_iter()
+            .map(|candidate| LegendEntry {
+                color: candidate.color,
+                swatch: candidate.swatch,
+                label: crop(image, candidate.label),
+            })
+            .collect(),
+    })
+}
+
+/// Candidates grouped if their swatches start at the same column
+fn aligned(candidates: Vec<Candidate>) -> Vec<Vec<Candidate>> {
+    let mut groups: Vec<Vec<Candidate>> = Vec::new();
+    for candidate in candidates
+        .into_iter()
+        .sorted_by_key(|candidate| candidate.swatch[0])
+    {
+        match groups.last_mut() {
+            Some(group) if candidate.swatch[0] - group[0].swatch[0] <= ALIGNMENT_TOLERANCE => {
+                group.push(candidate)
+            }
+            _ => groups.push(vec![candidate]),
+        }
+    }
+    groups
+}
+
+/// Aligned candidates far apart from each other belong to different stacks
+fn stacks(group: Vec<Candidate>, max_symbol_size: u32) -> Vec<Vec<Candidate>> {
+    let mut stacks: Vec<Vec<Candidate>> = Vec::new();
+    for candidate in group
+        .into_iter()
+        .sorted_by_key(|candidate| candidate.swatch[1])
+    {
+        match stacks.last_mut() {
+            Some(stack)
+                if candidate.swatch[1].saturating_sub(stack.last().unwrap().swatch[3])
+                    <= 3 * max_symbol_size =>
+            {
+                stack.push(candidate)
+            }
+            _ => stacks.push(vec![candidate]),
+        }
+    }
+    stacks
+}
+
+fn is_ink(c: &image::Rgba<u8>, settings: &crate::Settings) -> bool {
+    settings
+        .step1_step2_color_metric
+        .distance(&settings.background(), c)
+        >= settings.step1_step2_color_radius as f32
+}
+
+/// Ink right of the swatch, within the height of a text line around its center
+/// The label ends at a gap which is wider than the spaces between words.
+fn label_rect(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    swatch: [u32; 4],
+    max_symbol_size: u32,
+    settings: &crate::Settings,
+) -> Option<[u32; 4]> {
+    let (width, height) = image.dimensions();
+    let [_, top, right, bottom] = swatch;
+    let center = (top + bottom) / 2;
+    let half_height = max_symbol_size / 2 + 1;
+    let rows = center.saturating_sub(half_height)..=(center + half_height).min(height - 1);
+    let is_ink_column = |x: u32| {
+        rows.clone()
+            .any(|y| is_ink(image.get_pixel(x, y), settings))
+    };
+
+    // anti-aliased columns at the end of the swatch are skipped
+    let first = right + 1 + ALIGNMENT_TOLERANCE;
+    let start = (first..width.min(first + max_symbol_size)).find(|x| is_ink_column(*x))?;
+    let mut end = start;
+    let mut gap = 0;
+    for x in start + 1..width {
+        if is_ink_column(x) {
+            end = x;
+            gap = 0;
+        } else {
+            gap += 1;
+            if gap > max_symbol_size / 2 {
+                break;
+            }
+        }
+    }
+    let ink_rows = rows
+        .filter(|y| (start..=end).any(|x| is_ink(image.get_pixel(x, *y), settings)))
+        .collect_vec();
+    Some([start, *ink_rows.first()?, end, *ink_rows.last()?])
+}
+
+/// Most ink of text is neutral, black or gray
+fn is_text(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    [left, top, right, bottom]: [u32; 4],
+    settings: &crate::Settings,
+) -> bool {
+    let (neutral, ink) = (left..=right)
+        .cartesian_product(top..=bottom)
+        .map(|(x, y)| image.get_pixel(x, y))
+        .filter(|c| is_ink(c, settings))
+        .fold((0, 0), |(neutral, ink), c| {
+            let (min, max) = c.0[..3]
+                .iter()
+                .fold((u8::MAX, 0), |(min, max), v| (min.min(*v), max.max(*v)));
+            (neutral + (max - min <= MAX_TEXT_SATURATION) as u32, ink + 1)
+        });
+    2 * neutral > ink
+}
+
+/// Grows the rectangle to a surrounding frame of the legend, if there is one
+fn include_frame(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    [left, top, right, bottom]: [u32; 4],
+    max_distance: u32,
+    settings: &crate::Settings,
+) -> [u32; 4] {
+    let (width, height) = image.dimensions();
+    let coverage = |pixels: Vec<(u32, u32)>| {
+        let count = pixels.len() as f32;
+        pixels
+            .into_iter()
+            .filter(|(x, y)| is_ink(image.get_pixel(*x, *y), settings))
+            .count() as f32
+            / count
+            >= MIN_FRAME_COVERAGE
+    };
+    let row = |y: u32| coverage((left..=right).map(|x| (x, y)).collect());
+    let column = |x: u32| coverage((top..=bottom).map(|y| (x, y)).collect());
+    let top = (top.saturating_sub(max_distance)..top)
+        .find(|y| row(*y))
+        .unwrap_or(top);
+    let bottom = (bottom + 1..(bottom + 1 + max_distance).min(height))
+        .rev()
+        .find(|y| row(*y))
+        .unwrap_or(bottom);
+    let left = (left.saturating_sub(max_distance)..left)
+        .find(|x| column(*x))
+        .unwrap_or(left);
+    let right = (right + 1..(right + 1 + max_distance).min(width))
+        .rev()
+        .find(|x| column(*x))
+        .unwrap_or(right);
+    [left, top, right, bottom]
+}
+
+fn crop(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    [left, top, right, bottom]: [u32; 4],
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    image::imageops::crop_imm(image, left, top, right - left + 1, bottom - top + 1).to_image()
+}
+
+/// The legend is replaced by the background, hence its swatches are not part of the curves
+pub fn remove(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    legend: &Legend,
+    background: image::Rgba<u8>,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let [left, top, right, bottom] = legend.rect;
+    let mut cleaned = image.clone();
+    for (x, y) in (left..=right).cartesian_product(top..=bottom) {
+        cleaned.put_pixel(x, y, background);
+    }
+    cleaned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: image::Rgba<u8> = image::Rgba([200, 0, 0, 255]);
+    const BLUE: image::Rgba<u8> = image::Rgba([0, 0, 200, 255]);
+    const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+    const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+    /// Framed legend with a red and a blue entry and a red dashed curve
+    fn plot() -> image::RgbaImage {
+        image::RgbaImage::from_fn(200, 150, |x, y| {
+            let frame = ((x == 130 || x == 180) && (10..=45).contains(&y))
+                || ((y == 10 || y == 45) && (130..=180).contains(&x));
+            // vertical strokes of letters
+            let label =
+                |top: u32| (156..=170).contains(&x) && x % 2 == 0 && (top..top + 6).contains(&y);
+            let swatch = |top: u32| (140..=150).contains(&x) && (top..=top + 1).contains(&y);
+            let dashes = y == 100 && ((20..30).contains(&x) || (35..45).contains(&x));
+            if frame || label(18) || label(32) {
+                BLACK
+            } else if swatch(20) || dashes {
+                RED
+            } else if swatch(34) {
+                BLUE
+            } else {
+                WHITE
+            }
+        })
+    }
+
+    fn palette(colors: &[image::Rgba<u8>]) -> Vec<PaletteColor> {
+        colors
+            .iter()
+            .map(|&color| PaletteColor {
+                color,
+                pixel_count: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn entries_with_labels_are_found() {
+        let image = plot();
+        let settings = crate::Settings::default();
+        let legend = detect_legend(&image, &palette(&[RED, BLUE]), &settings).unwrap();
+        assert_eq!(legend.rect, [130, 10, 180, 45]);
+        let entries = legend
+            .entries
+            .iter()
+            .map(|entry| (entry.color, entry.swatch, entry.label.dimensions()))
+            .collect_vec();
+        assert_eq!(
+            entries,
+            [
+                (RED, [140, 20, 150, 21], (15, 6)),
+                (BLUE, [140, 34, 150, 35], (15, 6))
+            ]
+        );
+
+        let cleaned = remove(&image, &legend, WHITE);
+        assert_eq!(cleaned.get_pixel(145, 20), &WHITE);
+        assert_eq!(cleaned.get_pixel(130, 30), &WHITE);
+        assert_eq!(cleaned.get_pixel(25, 100), &RED);
+    }
+
+    #[test]
+    fn dashes_and_single_monochrome_entries_are_no_legend() {
+        let image = plot();
+        let monochrome = crate::Settings {
+            step1_monochrome: true,
+            ..Default::default()
+        };
+        assert!(detect_legend(&image, &palette(&[RED]), &monochrome).is_none());
+
+        // without the legend only the dashes remain, the next dash is no label
+        let mut without_legend = image.clone();
+        for (x, y) in (130..=180).cartesian_product(10..=45) {
+            without_legend.put_pixel(x, y, WHITE);
+        }
+        let settings = crate::Settings::default();
+        assert!(detect_legend(&without_legend, &palette(&[RED]), &settings).is_none());
+    }
+}
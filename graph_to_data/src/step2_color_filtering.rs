@@ -3,12 +3,54 @@ pub fn color_filtering(
     target_color: &image::Rgba<u8>,
     settings: &crate::Settings,
 ) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>> {
-    imageproc::map::map_pixels(image, |_, _, p| {
-        let diff = crate::color_distance(&p, target_color);
-        if diff < settings.step1_step2_color_radius {
-            crate::HIT
-        } else {
-            crate::MISSED
-        }
+    let mut known = std::collections::HashMap::new();
+    image::ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        *known.entry(*p).or_insert_with(|| {
+            let diff = settings.step1_step2_color_metric.distance(p, target_color);
+            if diff < settings.step1_step2_color_radius as f32 {
+                crate::HIT
+            } else {
+                crate::MISSED
+            }
+        })
     })
 }
+
+/// Estimated coverage of each pixel by the target color, i.e. how much of the target color is
+/// blended with the background, e.g. at anti-aliased edges of a line
+/// Pixels which are not such a blend, e.g. of another color, get weight 0
+pub fn color_weights(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    target_color: &image::Rgba<u8>,
+    settings: &crate::Settings,
+) -> image::ImageBuffer<image::Luma<f32>, Vec<f32>> {
+    let background = settings.background().0.map(|c| c as f32);
+    let direction = [0, 1, 2].map(|i| target_color.0[i] as f32 - background[i]);
+    let length_squared = direction.iter().map(|d| d * d).sum::<f32>();
+    // blends are rounded to whole color values, hence the residual is more tolerant than the radius
+    let max_residual = 3. * settings.step1_step2_color_radius as f32;
+    let mut weights = image::ImageBuffer::new(image.width(), image.height());
+    if length_squared == 0. {
+        return weights;
+    }
+    let mut known = std::collections::HashMap::new();
+    for (x, y, p) in image.enumerate_pixels() {
+        let weight = *known.entry(*p).or_insert_with(|| {
+            let offset = [0, 1, 2].map(|i| p.0[i] as f32 - background[i]);
+            let alpha = (0..3).map(|i| offset[i] * direction[i]).sum::<f32>() / length_squared;
+            let alpha = alpha.clamp(0., 1.);
+            let [r, g, b] = [0, 1, 2].map(|i| (background[i] + alpha * direction[i]).round() as u8);
+            let blend = image::Rgba([r, g, b, p.0[3]]);
+            // the residual is measured with the metric of the radius, e.g. in ΔE for CIELAB
+            let residual = settings.step1_step2_color_metric.distance(p, &blend);
+            if residual <= max_residual {
+                alpha
+            } else {
+                0.
+            }
+        });
+        weights.put_pixel(x, y, image::Luma([weight]));
+    }
+    weights
+}
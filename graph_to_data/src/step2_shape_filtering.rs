@@ -0,0 +1,199 @@
+use itertools::Itertools;
+
+use crate::step1_color_extraction::is_background;
+use crate::{DetectionMode, HIT, MISSED};
+
+/// Ticks are thin straight segments attached to the frame
+const MAX_TICK_THICKNESS: u32 = 3;
+
+/// Monochrome plots draw the frame, ticks and text in the same color as the curves,
+/// hence they are found by their shape within all gray levels which differ from the background:
+/// - long horizontal and vertical lines are the frame, pixels where a curve crosses it are kept
+/// - thin components which touch the removed frame are ticks
+/// - small components are text, not applied to markers in scatter mode
+///
+/// Returns the mask of these pixels, the frame is usually anti-aliased and therefore has several
+/// gray levels, hence it is found once for all of them
+pub fn frame_and_text(
+    image: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    settings: &crate::Settings,
+) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>> {
+    let color_radius = settings.step1_step2_color_radius as f32;
+    let background = settings.background();
+    let ink = image::ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let c = image.get_pixel(x, y);
+        if is_background(
+            c,
+            &background,
+            color_radius,
+            settings.step1_step2_color_metric,
+            true,
+            true,
+        ) {
+            MISSED
+        } else {
+            HIT
+        }
+    });
+    let image = &ink;
+    let (width, height) = image.dimensions();
+    let is_hit = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && x < width as i64
+            && y < height as i64
+            && image.get_pixel(x as u32, y as u32) == &HIT
+    };
+
+    let mut frame = image::ImageBuffer::<image::Luma<u8>, Vec<u8>>::new(width, height);
+    let min_width = (settings.step2_frame_min_length_fraction * width as f32).max(2.) as u32;
+    let min_height = (settings.step2_frame_min_length_fraction * height as f32).max(2.) as u32;
+    for y in 0..height {
+        for (_, run) in &(0..width).chunk_by(|&x| image.get_pixel(x, y) == &HIT) {
+            let run = run.collect_vec();
+            if image.get_pixel(run[0], y) == &HIT && run.len() as u32 >= min_width {
+                run.into_iter().for_each(|x| frame.put_pixel(x, y, HIT));
+            }
+        }
+    }
+    for x in 0..width {
+        for (_, run) in &(0..height).chunk_by(|&y| image.get_pixel(x, y) == &HIT) {
+            let run = run.collect_vec();
+            if image.get_pixel(x, run[0]) == &HIT && run.len() as u32 >= min_height {
+                run.into_iter().for_each(|y| frame.put_pixel(x, y, HIT));
+            }
+        }
+    }
+    let is_frame = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && x < width as i64
+            && y < height as i64
+            && frame.get_pixel(x as u32, y as u32) == &HIT
+    };
+
+    let mut cleaned = image.clone();
+    for (x, y, p) in frame.enumerate_pixels() {
+        if p != &HIT {
+            continue;
+        }
+        let (x, y) = (x as i64, y as i64);
+        // the thickness of the frame line is skipped in both directions
+        let beyond = |dx: i64, dy: i64| {
+            (1..)
+                .map(|step| (x + step * dx, y + step * dy))
+                .find(|&(x, y)| !is_frame(x, y))
+                .is_some_and(|(x, y)| is_hit(x, y))
+        };
+        let crossed = (beyond(0, -1) && beyond(0, 1)) || (beyond(-1, 0) && beyond(1, 0));
+        if !crossed {
+            cleaned.put_pixel(x as u32, y as u32, MISSED);
+        }
+    }
+
+    let labels = imageproc::region_labelling::connected_components(
+        &cleaned,
+        imageproc::region_labelling::Connectivity::Eight,
+        MISSED,
+    );
+    let mut components: Vec<Vec<(u32, u32)>> = Vec::new();
+    for (x, y, label) in labels.enumerate_pixels() {
+        let label = label.0[0] as usize;
+        if label == 0 {
+            continue;
+        }
+        if components.len() < label {
+            components.resize_with(label, Vec::new);
+        }
+        components[label - 1].push((x, y));
+    }
+    for pixels in components {
+        let size = |coordinate: fn(&(u32, u32)) -> u32| {
+            let (min, max) = pixels
+                .iter()
+                .map(coordinate)
+                .fold((u32::MAX, 0), |(min, max), c| (min.min(c), max.max(c)));
+            max - min + 1
+        };
+        let (component_width, component_height) = (size(|p| p.0), size(|p| p.1));
+        let touches_frame = pixels.iter().any(|&(x, y)| {
+            (-1..=1)
+                .cartesian_product(-1..=1)
+                .any(|(dx, dy)| is_frame(x as i64 + dx, y as i64 + dy))
+        });
+        let is_tick = touches_frame && component_width.min(component_height) <= MAX_TICK_THICKNESS;
+        let is_text = settings.mode != DetectionMode::Scatter
+            && component_width.max(component_height) <= settings.step2_max_symbol_size;
+        if is_tick || is_text {
+            for (x, y) in pixels {
+                cleaned.put_pixel(x, y, MISSED);
+            }
+        }
+    }
+    image::ImageBuffer::from_fn(width, height, |x, y| {
+        if image.get_pixel(x, y) == &HIT && cleaned.get_pixel(x, y) == &MISSED {
+            HIT
+        } else {
+            MISSED
+        }
+    })
+}
+
+pub fn remove(
+    image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    mask: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>> {
+    let mut cleaned = image.clone();
+    cleaned
+        .pixels_mut()
+        .zip(mask.pixels())
+        .filter(|(_, m)| *m == &HIT)
+        .for_each(|(p, _)| *p = MISSED);
+    cleaned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gray frame on the left and bottom with a tick, a black diagonal curve and a letter
+    fn plot() -> image::RgbaImage {
+        image::RgbaImage::from_fn(100, 80, |x, y| {
+            let frame = (x == 10 && (5..=75).contains(&y)) || (y == 75 && (10..=95).contains(&x));
+            let tick = x == 30 && (76..=78).contains(&y);
+            let curve = (12..=90).contains(&x) && y == 65 - x / 2;
+            let letter = (y == 5 && (50..=54).contains(&x)) || (x == 52 && (5..=9).contains(&y));
+            if frame {
+                image::Rgba([100, 100, 100, 255])
+            } else if tick || curve || letter {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn frame_ticks_and_text_are_found_but_not_the_curve() {
+        let mask = frame_and_text(&plot(), &crate::Settings::default());
+        for (x, y) in [(10, 40), (50, 75), (30, 77), (52, 7)] {
+            assert_eq!(mask.get_pixel(x, y), &HIT, "{x} {y}");
+        }
+        assert!((12..=90).all(|x| mask.get_pixel(x, 65 - x / 2) == &MISSED));
+
+        let removed = remove(&image::GrayImage::from_pixel(100, 80, HIT), &mask);
+        assert_eq!(removed.get_pixel(10, 40), &MISSED);
+        assert_eq!(removed.get_pixel(40, 40), &HIT);
+    }
+
+    #[test]
+    fn markers_are_no_text_in_scatter_mode() {
+        let settings = crate::Settings {
+            mode: DetectionMode::Scatter,
+            ..Default::default()
+        };
+        let mask = frame_and_text(&plot(), &settings);
+        assert_eq!(mask.get_pixel(52, 7), &MISSED);
+        assert_eq!(mask.get_pixel(30, 77), &HIT);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::BarOrientation;
+
+/// Edges within this distance in pixels are the same baseline
+const BASELINE_TOLERANCE: f32 = 1.;
+
+/// Bar in pixel coordinates of the cropped image
+/// 'start'/'end' are along the category axis (x for vertical bars),
+/// 'low'/'high' are the mean extents along the value axis (y for vertical bars)
+#[derive(Debug, Clone)]
+pub struct BarPixels {
+    pub start: u32, // this is included
+    pub end: u32,   // this is also included
+    pub low: f32,
+    pub high: f32,
+}
+
+/// Filled rectangles of a single color
+/// Neighboring columns (rows for horizontal bars) with equal extent are combined into one bar
+pub fn find_bars(
+    image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    settings: &crate::Settings,
+) -> Vec<BarPixels> {
+    let orientation = settings.step3_bar_orientation;
+    let (lines, length) = match orientation {
+        BarOrientation::Vertical => (image.width(), image.height()),
+        BarOrientation::Horizontal => (image.height(), image.width()),
+    };
+    let is_hit = |line: u32, position: u32| {
+        let (x, y) = match orientation {
+            BarOrientation::Vertical => (line, position),
+            BarOrientation::Horizontal => (position, line),
+        };
+        image.get_pixel(x, y) == &crate::HIT
+    };
+    // longest run per column/row, `Option::is_none_or` would require Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    let runs = (0..lines).map(|line| {
+        let mut longest: Option<(u32, u32)> = None;
+        let mut start = None;
+        for position in 0..=length {
+            match (position < length && is_hit(line, position), start) {
+                (true, None) => start = Some(position),
+                (false, Some(s)) => {
+                    if longest.map_or(true, |(l, h)| h - l < position - 1 - s) {
+                        longest = Some((s, position - 1));
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        longest
+    });
+
+    let mut bars = Vec::new();
+    let mut current: Option<(u32, Vec<(u32, u32)>)> = None;
+    let mut complete = |current: Option<(u32, Vec<(u32, u32)>)>| {
+        if let Some((start, extents)) = current {
+            if extents.len() as u32 >= settings.step3_bar_min_width.max(1) {
+                let n = extents.len() as f32;
+                bars.push(BarPixels {
+                    start,
+                    end: start + extents.len() as u32 - 1,
+                    low: extents.iter().map(|e| e.0 as f32).sum::<f32>() / n,
+                    high: extents.iter().map(|e| e.1 as f32).sum::<f32>() / n,
+                });
+            }
+        }
+    };
+    for (line, run) in runs.enumerate() {
+        let line = line as u32;
+        match (run, &mut current) {
+            (Some(run), Some((_, extents)))
+                if extents.last().is_some_and(|last| {
+                    last.0.abs_diff(run.0) <= 1 && last.1.abs_diff(run.1) <= 1
+                }) =>
+            {
+                extents.push(run)
+            }
+            (run, current) => {
+                complete(current.take());
+                *current = run.map(|run| (line, vec![run]));
+            }
+        }
+    }
+    complete(current);
+    bars
+}
+
+/// Position of the edge shared by most bars along the value axis, positive bars start there with
+/// their bottom (left for horizontal bars), negative bars with their top
+/// Ties are decided for bars growing up or right, None if no edge is shared.
+pub fn baseline(bars: &[BarPixels], orientation: BarOrientation) -> Option<f32> {
+    let edges = bars
+        .iter()
+        .flat_map(|bar| [(bar.low - 0.5, false), (bar.high + 0.5, true)])
+        .collect::<Vec<_>>();
+    edges
+        .iter()
+        .map(|&(edge, is_high)| {
+            let count = edges
+                .iter()
+                .filter(|(other, _)| (other - edge).abs() <= BASELINE_TOLERANCE)
+                .count();
+            let usual = is_high == (orientation == BarOrientation::Vertical);
+            (count, usual, edge)
+        })
+        .filter(|(count, _, _)| *count >= 2)
+        .max_by_key(|(count, usual, _)| (*count, *usual))
+        .map(|(_, _, edge)| edge)
+}
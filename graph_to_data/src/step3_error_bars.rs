@@ -0,0 +1,213 @@
+use crate::{HIT, MISSED};
+
+/// Thin vertical segment with optional caps in pixel coordinates of the cropped image
+#[derive(Debug, Clone)]
+pub struct ErrorBarPixels {
+    pub x_start: u32, // this is included
+    pub x_end: u32,   // this is also included
+    pub y_min: u32,
+    pub y_max: u32,
+    pub has_caps: bool,
+    /// Removed pixels, i.e. the segment and caps without the rows crossed by a curve or marker
+    pub pixels: Vec<(u32, u32)>,
+}
+impl ErrorBarPixels {
+    pub fn center_x(&self) -> f32 {
+        (self.x_start + self.x_end) as f32 / 2.
+    }
+
+    /// Whether a curve point or marker centroid lies on the bar
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x_start as f32 - 1.
+            && x <= self.x_end as f32 + 1.
+            && y >= self.y_min as f32
+            && y <= self.y_max as f32
+    }
+}
+
+struct Candidate {
+    x_start: u32,
+    x_end: u32,
+    y_min: u32,
+    y_max: u32,
+}
+
+/// Finds error bars and returns a copy of the image without them
+/// Rows in which a curve or marker crosses the bar are kept, so the point itself is not lost
+pub fn remove_error_bars(
+    image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    settings: &crate::Settings,
+) -> (
+    image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    Vec<ErrorBarPixels>,
+) {
+    let (width, height) = image.dimensions();
+    let max_width = settings.step3_error_bar_max_width.max(1);
+    let min_length = settings.step3_error_bar_min_length.max(2);
+    let is_hit = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && x < width as i64
+            && y < height as i64
+            && image.get_pixel(x as u32, y as u32) == &HIT
+    };
+
+    // long vertical runs, neighboring columns with (almost) equal extents are combined
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for x in 0..width {
+        let mut start = None;
+        for y in 0..=height {
+            match (y < height && image.get_pixel(x, y) == &HIT, start) {
+                (true, None) => start = Some(y),
+                (false, Some(y_min)) => {
+                    start = None;
+                    let y_max = y - 1;
+                    if y_max - y_min + 1 < min_length {
+                        continue;
+                    }
+                    if let Some(candidate) = candidates.iter_mut().find(|c| {
+                        c.x_end + 1 == x
+                            && c.y_min.abs_diff(y_min) <= 1
+                            && c.y_max.abs_diff(y_max) <= 1
+                    }) {
+                        candidate.x_end = x;
+                        candidate.y_min = candidate.y_min.min(y_min);
+                        candidate.y_max = candidate.y_max.max(y_max);
+                    } else {
+                        candidates.push(Candidate {
+                            x_start: x,
+                            x_end: x,
+                            y_min,
+                            y_max,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut cleaned = image.clone();
+    let mut error_bars = Vec::new();
+    for Candidate {
+        x_start,
+        x_end,
+        y_min,
+        y_max,
+    } in candidates
+    {
+        if x_end - x_start + 1 > max_width {
+            continue;
+        }
+        let (left, right) = (x_start as i64 - 1, x_end as i64 + 1);
+        let (top, bottom) = (y_min as i64, y_max as i64);
+        // a steep curve or a step continues diagonally beyond the ends, an error bar does not
+        if (left..=right).any(|x| is_hit(x, top - 1) || is_hit(x, bottom + 1)) {
+            continue;
+        }
+        let max_cap_length = (y_max - y_min + 1) as i64 / 2;
+        // rows at the end of the bar with hits next to it and how far these reach to the left/right
+        let cap = |rows: Vec<i64>| -> Option<(Vec<i64>, i64, i64)> {
+            let rows = rows
+                .into_iter()
+                .take_while(|&y| is_hit(left, y) || is_hit(right, y))
+                .collect::<Vec<_>>();
+            let reach = |direction: i64, start: i64| {
+                (0..)
+                    .take_while(|offset| {
+                        rows.iter().any(|&y| is_hit(start + direction * offset, y))
+                    })
+                    .take(max_cap_length as usize + 1)
+                    .count() as i64
+            };
+            let (reach_left, reach_right) = (reach(-1, left), reach(1, right));
+            match (reach_left > 0, reach_right > 0) {
+                // a horizontal line instead of a cap
+                _ if reach_left > max_cap_length || reach_right > max_cap_length => None,
+                // a curve which ends at the bar, e.g. the step of a step plot
+                (true, false) | (false, true) => None,
+                _ => Some((rows, reach_left, reach_right)),
+            }
+        };
+        let cap_rows = max_width as i64;
+        let Some(top_cap) = cap((top..top + cap_rows).collect()) else {
+            continue;
+        };
+        let Some(bottom_cap) = cap((bottom - cap_rows + 1..=bottom).rev().collect()) else {
+            continue;
+        };
+
+        let mut pixels = Vec::new();
+        for (rows, reach_left, reach_right) in [&top_cap, &bottom_cap] {
+            for &y in rows {
+                for x in left + 1 - reach_left..right + reach_right {
+                    if is_hit(x, y) {
+                        pixels.push((x as u32, y as u32));
+                    }
+                }
+            }
+        }
+        for y in top..=bottom {
+            if top_cap.0.contains(&y) || bottom_cap.0.contains(&y) {
+                continue;
+            }
+            // keep rows in which a curve or marker crosses the bar
+            if is_hit(left, y) || is_hit(right, y) {
+                continue;
+            }
+            pixels.extend((x_start..=x_end).map(|x| (x, y as u32)));
+        }
+        for &(x, y) in &pixels {
+            *cleaned.get_pixel_mut(x, y) = MISSED;
+        }
+        error_bars.push(ErrorBarPixels {
+            x_start,
+            x_end,
+            y_min,
+            y_max,
+            has_caps: top_cap.1 > 0 || bottom_cap.1 > 0,
+            pixels,
+        });
+    }
+    (cleaned, error_bars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A curve along row 30 crossing a capped bar at x = 30 and a bar without caps at x = 10,
+    /// a vertical step at x = 50 continues diagonally
+    fn mask() -> image::GrayImage {
+        image::GrayImage::from_fn(60, 60, |x, y| {
+            let curve = y == 30;
+            let capped = (x == 30 && (15..=45).contains(&y))
+                || ((27..=33).contains(&x) && (y == 15 || y == 45));
+            let uncapped = x == 10 && (20..=40).contains(&y);
+            let step = (x == 50 && (5..=25).contains(&y)) || (x, y) == (51, 26);
+            if curve || capped || uncapped || step {
+                HIT
+            } else {
+                MISSED
+            }
+        })
+    }
+
+    #[test]
+    fn bars_are_removed_and_the_curve_is_kept() {
+        let (cleaned, error_bars) = remove_error_bars(&mask(), &crate::Settings::default());
+        let extents = error_bars
+            .iter()
+            .map(|bar| (bar.x_start, bar.x_end, bar.y_min, bar.y_max, bar.has_caps))
+            .collect::<Vec<_>>();
+        assert_eq!(extents, [(10, 10, 20, 40, false), (30, 30, 15, 45, true)]);
+        assert!(error_bars[1].contains(30., 30.));
+        assert!(!error_bars[1].contains(35., 30.));
+
+        assert!((0..60).all(|x| cleaned.get_pixel(x, 30) == &HIT));
+        for (x, y) in [(30, 20), (10, 25), (27, 15), (33, 45)] {
+            assert_eq!(cleaned.get_pixel(x, y), &MISSED, "{x} {y}");
+        }
+        assert!((5..=25).all(|y| cleaned.get_pixel(50, y) == &HIT));
+    }
+}
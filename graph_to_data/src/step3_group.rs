@@ -3,16 +3,56 @@ use itertools::Itertools;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct X(pub u32);
 
+/// Number of pixels above and below a vertical run which may be partially covered by the line
+const ANTI_ALIASING_PIXELS: u32 = 2;
+
+/// Sums for the color-similarity-weighted mean of y
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Centroid {
+    weight: f32,
+    weighted_y: f32,
+}
+impl Centroid {
+    /// Centroid at `y`, e.g. of a run with `weight` pixels
+    pub fn new(weight: f32, y: f32) -> Self {
+        Self {
+            weight,
+            weighted_y: weight * y,
+        }
+    }
+
+    fn add(self, other: Centroid) -> Centroid {
+        Centroid {
+            weight: self.weight + other.weight,
+            weighted_y: self.weighted_y + other.weighted_y,
+        }
+    }
+
+    pub fn y(&self) -> Option<f32> {
+        (self.weight > 0.).then(|| self.weighted_y / self.weight)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VerticalComponentCombined {
     pub y_min: u32, // this is include
     pub y_max: u32, // this is also include
+    pub centroid: Centroid,
 }
 impl VerticalComponentCombined {
     fn new(v: VerticalComponent) -> Self {
-        let VerticalComponent { y_min, y_max } = v;
-        Self { y_min, y_max }
+        let VerticalComponent {
+            y_min,
+            y_max,
+            centroid,
+        } = v;
+        Self {
+            y_min,
+            y_max,
+            centroid,
+        }
     }
+    #[allow(clippy::implicit_saturating_sub)]
     fn distance_to(&self, other: &VerticalComponent) -> u32 {
         if self.y_min > other.y_max {
             self.y_min - other.y_max
@@ -22,6 +62,7 @@ impl VerticalComponentCombined {
             0
         }
     }
+    #[allow(clippy::implicit_saturating_sub)]
     fn distance_to_other(&self, other: &VerticalComponentCombined) -> u32 {
         if self.y_min > other.y_max {
             self.y_min - other.y_max
@@ -37,27 +78,49 @@ impl VerticalComponentCombined {
 
     fn merge(new: Vec<VerticalComponent>) -> VerticalComponentCombined {
         let y_min = new.iter().map(|x| x.y_min).min().unwrap();
-        let y_max = new.into_iter().map(|x| x.y_max).max().unwrap();
-        Self { y_min, y_max }
+        let y_max = new.iter().map(|x| x.y_max).max().unwrap();
+        let centroid = new
+            .into_iter()
+            .fold(Centroid::default(), |c, x| c.add(x.centroid));
+        Self {
+            y_min,
+            y_max,
+            centroid,
+        }
     }
 
     fn combine(self, other: VerticalComponentCombined) -> VerticalComponentCombined {
         let y_min = self.y_min.min(other.y_min);
         let y_max = self.y_max.min(other.y_max);
-        Self { y_min, y_max }
+        let centroid = self.centroid.add(other.centroid);
+        Self {
+            y_min,
+            y_max,
+            centroid,
+        }
     }
 
     fn convert(self) -> VerticalComponent {
-        let Self { y_min, y_max } = self;
-        VerticalComponent { y_min, y_max }
+        let Self {
+            y_min,
+            y_max,
+            centroid,
+        } = self;
+        VerticalComponent {
+            y_min,
+            y_max,
+            centroid,
+        }
     }
 }
 #[derive(Debug, Clone)]
 pub struct VerticalComponent {
     pub y_min: u32, // this is include
     pub y_max: u32, // this is also include
+    pub centroid: Centroid,
 }
 impl VerticalComponent {
+    #[allow(clippy::implicit_saturating_sub)]
     fn distance_to(&self, other: &Self) -> u32 {
         if self.y_min > other.y_max {
             self.y_min - other.y_max
@@ -83,23 +146,56 @@ impl VerticalComponents {
 impl VerticalComponentList {
     /// Convert black-white image in List of white points
     /// Vertically connected stripes of white points are combined into a single item
-    pub fn convert(image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>) -> Self {
+    /// The centroid is weighted by the color similarity of the pixels of a stripe and includes the
+    /// anti-aliased pixels directly above and below it
+    pub fn convert(
+        image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        weights: &image::ImageBuffer<image::Luma<f32>, Vec<f32>>,
+    ) -> Self {
         let components = (0..image.width())
             .map(|x| {
                 let mut components = Vec::new();
                 let mut current_component = Vec::new();
-                #[inline(always)]
-                fn complete_component(
-                    current_component: &mut Vec<u32>,
-                    components: &mut Vec<VerticalComponent>,
-                ) {
-                    let current_component = std::mem::take(current_component);
-                    if !current_component.is_empty() {
-                        let y_min = *current_component.first().unwrap();
-                        let y_max = *current_component.last().unwrap();
-                        components.push(VerticalComponent { y_min, y_max });
-                    }
-                }
+                let complete_component =
+                    |current_component: &mut Vec<u32>, components: &mut Vec<VerticalComponent>| {
+                        let current_component = std::mem::take(current_component);
+                        if !current_component.is_empty() {
+                            let y_min = *current_component.first().unwrap();
+                            let y_max = *current_component.last().unwrap();
+                            // pixels of the run count fully if the color similarity is unknown
+                            let mut centroid = current_component
+                                .iter()
+                                .map(|&y| {
+                                    let weight = weights.get_pixel(x, y).0[0];
+                                    let weight = if weight > 0. { weight } else { 1. };
+                                    Centroid {
+                                        weight,
+                                        weighted_y: weight * y as f32,
+                                    }
+                                })
+                                .fold(Centroid::default(), Centroid::add);
+                            let above = (1..=ANTI_ALIASING_PIXELS)
+                                .map_while(|offset| y_min.checked_sub(offset));
+                            let below = (1..=ANTI_ALIASING_PIXELS)
+                                .map(|offset| y_max + offset)
+                                .take_while(|&y| y < image.height());
+                            for y in above
+                                .take_while(|&y| image.get_pixel(x, y) != &crate::HIT)
+                                .chain(below.take_while(|&y| image.get_pixel(x, y) != &crate::HIT))
+                            {
+                                let weight = weights.get_pixel(x, y).0[0];
+                                centroid = centroid.add(Centroid {
+                                    weight,
+                                    weighted_y: weight * y as f32,
+                                });
+                            }
+                            components.push(VerticalComponent {
+                                y_min,
+                                y_max,
+                                centroid,
+                            });
+                        }
+                    };
                 for y in 0..image.height() {
                     if image.get_pixel(x, y) == &crate::HIT {
                         current_component.push(y);
@@ -179,7 +275,7 @@ impl VerticalComponentList {
                 v.ys.extend(vec![MultiNode::default(); (width - end.0) as usize])
             });
             for comp in &new_components {
-                debug_assert_eq!(comp.ys.len(), width as _);
+                debug_assert_eq!(comp.ys.len(), width as usize);
             }
             debug_assert_eq!(new_components.len(), count);
             components.extend(new_components);
@@ -316,12 +412,13 @@ struct Range {
 
 pub fn group_large_components_and_remaining(
     image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    weights: &image::ImageBuffer<image::Luma<f32>, Vec<f32>>,
     settings: &crate::Settings,
 ) -> (Vec<GraphMultiNode>, Vec<CombinedVerticals>) {
     let width = image.width();
     let min_width = (width as f32 * settings.step3_min_width_fraction) as u32;
 
-    let mut verticals = VerticalComponentList::convert(image);
+    let mut verticals = VerticalComponentList::convert(image, weights);
     let ranges = verticals.find_long_components(min_width);
 
     let components = verticals.extract_components(ranges, width);
@@ -339,10 +436,44 @@ pub(crate) enum Distance {
 #[derive(Default, Clone)]
 pub struct MultiNode {
     verticals: Vec<VerticalComponent>,
+    /// Bridged gap of a dashed or dotted curve instead of pixels of the image
+    interpolated: bool,
+    /// Pixels of a remaining vertical which was stitched to a large component
+    remainder: bool,
 }
 impl MultiNode {
     fn new(v: VerticalComponent) -> Self {
-        Self { verticals: vec![v] }
+        Self {
+            verticals: vec![v],
+            interpolated: false,
+            remainder: false,
+        }
+    }
+
+    /// Node of one vertical run of pixels from `y_min` to `y_max`, both included
+    pub fn from_run(y_min: u32, y_max: u32, centroid: Centroid) -> Self {
+        Self::new(VerticalComponent {
+            y_min,
+            y_max,
+            centroid,
+        })
+    }
+
+    pub(crate) fn interpolated(y: f32, height: u32) -> Self {
+        let y = y.clamp(0., height.saturating_sub(1) as f32);
+        let pixel = y.round() as u32;
+        Self {
+            interpolated: true,
+            ..Self::from_run(pixel, pixel, Centroid::new(1., y))
+        }
+    }
+
+    pub fn is_interpolated(&self) -> bool {
+        self.interpolated
+    }
+
+    pub fn is_remainder(&self) -> bool {
+        self.remainder
     }
 
     fn distance(&self, r: &MultiNode) -> u32 {
@@ -355,9 +486,42 @@ impl MultiNode {
     }
 
     fn combine(&mut self, o: MultiNode) {
+        // only interpolated or a remainder if all pixels are
+        (self.interpolated, self.remainder) =
+            match (self.verticals.is_empty(), o.verticals.is_empty()) {
+                (true, _) => (o.interpolated, o.remainder),
+                (false, true) => (self.interpolated, self.remainder),
+                (false, false) => (
+                    self.interpolated && o.interpolated,
+                    self.remainder && o.remainder,
+                ),
+            };
         self.verticals.extend(o.verticals)
     }
 
+    /// Distinct vertical runs, overlapping or touching verticals are merged
+    pub fn runs(&self) -> Vec<VerticalComponent> {
+        let mut runs: Vec<VerticalComponent> = Vec::new();
+        for v in self.verticals.iter().sorted_by_key(|v| v.y_min) {
+            match runs.last_mut() {
+                Some(run) if v.y_min <= run.y_max + 1 => {
+                    run.y_max = run.y_max.max(v.y_max);
+                    run.centroid = run.centroid.add(v.centroid);
+                }
+                _ => runs.push(v.clone()),
+            }
+        }
+        runs
+    }
+
+    /// Color-similarity-weighted mean of all verticals, with sub-pixel accuracy
+    pub fn centroid(&self) -> Option<f32> {
+        self.verticals
+            .iter()
+            .fold(Centroid::default(), |c, v| c.add(v.centroid))
+            .y()
+    }
+
     pub fn mean(&self) -> Option<u32> {
         if let Some(min) = self.verticals.iter().map(|x| x.y_min).min() {
             let max = self.verticals.iter().map(|x| x.y_max).max().unwrap();
@@ -461,11 +625,20 @@ impl GraphMultiNode {
         }
     }
 
+    /// Graph without any nodes, the nodes are set by `ys[x]`
+    pub fn empty(width: usize) -> Self {
+        Self {
+            ys: vec![MultiNode::default(); width],
+        }
+    }
+
     pub(crate) fn merge(&mut self, v: CombinedVerticals) {
         let CombinedVerticals { x_start, combined } = v;
         for (x_offset, v) in combined.into_iter().enumerate() {
             let x = x_offset + x_start.0 as usize;
-            self.ys[x].verticals.push(v.convert())
+            let ys = &mut self.ys[x];
+            ys.remainder = ys.verticals.is_empty() || ys.remainder;
+            ys.verticals.push(v.convert())
         }
     }
 
@@ -480,41 +653,6 @@ impl GraphMultiNode {
         self.ys
             .iter_mut()
             .zip(other.ys)
-            .for_each(|(s, o)| s.verticals.extend(o.verticals));
-    }
-
-    pub(crate) fn to_plot(
-        &self,
-        x_limits: (f32, f32),
-        y_limits: (f32, f32),
-        steps_x: u32,
-        steps_y: u32,
-    ) -> Vec<(f32, f32)> {
-        assert_eq!(self.ys.len(), steps_x as usize);
-        self.ys
-            .iter()
-            .enumerate()
-            .flat_map(|(x, ys)| {
-                if let Some(y) = ys.mean() {
-                    fn convert(x: u32, limits: (f32, f32), n: u32, min_max: bool) -> f32 {
-                        let delta = limits.1 - limits.0;
-                        assert!(delta.is_finite());
-                        assert!(delta > 0.);
-                        let delta_divided_n = delta / (n + 1) as f32;
-                        let t = (x + 1) as f32 * delta_divided_n;
-                        if min_max {
-                            limits.1 - t
-                        } else {
-                            limits.0 + t
-                        }
-                    }
-                    let x = convert(x as u32, x_limits, steps_x, false);
-                    let y = convert(y, y_limits, steps_y, true);
-                    Some((x, y))
-                } else {
-                    None
-                }
-            })
-            .collect()
+            .for_each(|(s, o)| s.combine(o));
     }
 }
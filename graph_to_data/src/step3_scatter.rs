@@ -0,0 +1,103 @@
+/// Connected blob of pixels of a single color, e.g. a circle, square or cross
+#[derive(Debug, Clone)]
+pub struct Blob {
+    /// Sub-pixel centroid within the cropped image
+    pub x: f32,
+    pub y: f32,
+    pub pixel_count: u32,
+    pub pixels: Vec<(u32, u32)>,
+}
+
+pub fn find_markers(
+    image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    settings: &crate::Settings,
+) -> Vec<Blob> {
+    let labels = imageproc::region_labelling::connected_components(
+        image,
+        imageproc::region_labelling::Connectivity::Eight,
+        crate::MISSED,
+    );
+    let mut blobs: Vec<Vec<(u32, u32)>> = Vec::new();
+    for (x, y, label) in labels.enumerate_pixels() {
+        let label = label.0[0] as usize;
+        if label == 0 {
+            continue;
+        }
+        if blobs.len() < label {
+            blobs.resize_with(label, Vec::new);
+        }
+        blobs[label - 1].push((x, y));
+    }
+    let max_size = (settings.step3_marker_max_size_fraction * image.height() as f32).max(1.) as u32;
+    blobs
+        .into_iter()
+        .filter(|pixels| pixels.len() as u32 >= settings.step3_marker_min_pixels)
+        .filter(|pixels| {
+            let size = |coordinate: fn(&(u32, u32)) -> u32| {
+                let (min, max) = pixels
+                    .iter()
+                    .map(coordinate)
+                    .fold((u32::MAX, 0), |(min, max), c| (min.min(c), max.max(c)));
+                max - min + 1
+            };
+            size(|p| p.0) <= max_size && size(|p| p.1) <= max_size
+        })
+        .map(|pixels| {
+            let pixel_count = pixels.len() as u32;
+            let x = pixels.iter().map(|p| p.0 as f32).sum::<f32>() / pixel_count as f32;
+            let y = pixels.iter().map(|p| p.1 as f32).sum::<f32>() / pixel_count as f32;
+            Blob {
+                x,
+                y,
+                pixel_count,
+                pixels,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(is_hit: impl Fn(u32, u32) -> bool) -> image::GrayImage {
+        image::GrayImage::from_fn(200, 200, |x, y| {
+            if is_hit(x, y) {
+                crate::HIT
+            } else {
+                crate::MISSED
+            }
+        })
+    }
+
+    #[test]
+    fn markers_are_found_at_their_centroids() {
+        let markers = mask(|x, y| {
+            let square = (20..25).contains(&x) && (30..35).contains(&y);
+            let cross =
+                (x == 100 && (96..=104).contains(&y)) || (y == 100 && (96..=104).contains(&x));
+            square || cross
+        });
+        let blobs = find_markers(&markers, &crate::Settings::default());
+        let centroids = blobs
+            .iter()
+            .map(|blob| (blob.x, blob.y, blob.pixel_count))
+            .collect::<Vec<_>>();
+        assert_eq!(centroids, [(22., 32., 25), (100., 100., 17)]);
+    }
+
+    #[test]
+    fn diagonal_pixels_are_one_marker() {
+        let diagonal = mask(|x, y| x == y && (50..56).contains(&x));
+        let blobs = find_markers(&diagonal, &crate::Settings::default());
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].pixel_count, 6);
+    }
+
+    #[test]
+    fn noise_and_lines_are_no_markers() {
+        // the maximal size is 10 pixels, the minimal count 4 pixels
+        let other = mask(|x, y| (x == 10 && y == 10) || (y == 150 && (20..80).contains(&x)));
+        assert!(find_markers(&other, &crate::Settings::default()).is_empty());
+    }
+}
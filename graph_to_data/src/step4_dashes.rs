@@ -0,0 +1,284 @@
+use itertools::Itertools;
+
+use crate::{
+    step3_group::{CombinedVerticals, GraphMultiNode, MultiNode},
+    Settings,
+};
+
+/// Columns at the end of a piece which are used for its direction
+const DIRECTION_COLUMNS: usize = 8;
+
+enum Source {
+    Graph(GraphMultiNode),
+    Vertical(CombinedVerticals),
+}
+
+/// A stitched graph or a remaining dash/dot with the centroid of each of its columns
+struct Piece {
+    source: Source,
+    start: usize,
+    end: usize, // this is included
+    samples: Vec<(f32, f32)>,
+}
+impl Piece {
+    fn from_graph(graph: GraphMultiNode) -> Option<Self> {
+        let samples = samples(&graph);
+        let (start, end) = (samples.first()?.0 as usize, samples.last()?.0 as usize);
+        Some(Self {
+            source: Source::Graph(graph),
+            start,
+            end,
+            samples,
+        })
+    }
+
+    fn from_vertical(vertical: CombinedVerticals) -> Option<Self> {
+        let samples = vertical
+            .combined
+            .iter()
+            .enumerate()
+            .map(|(x, v)| {
+                let y = v.centroid.y().unwrap_or(v.mean() as f32);
+                ((x + vertical.x_start.0 as usize) as f32, y)
+            })
+            .collect_vec();
+        let (start, end) = (samples.first()?.0 as usize, samples.last()?.0 as usize);
+        Some(Self {
+            source: Source::Vertical(vertical),
+            start,
+            end,
+            samples,
+        })
+    }
+}
+
+fn samples(graph: &GraphMultiNode) -> Vec<(f32, f32)> {
+    graph
+        .ys
+        .iter()
+        .enumerate()
+        .filter(|(_, ys)| !ys.is_interpolated())
+        .filter_map(|(x, ys)| ys.centroid().map(|y| (x as f32, y)))
+        .collect()
+}
+
+/// Least squares line through the samples, returns the value at `x` and the slope
+fn fit(samples: &[(f32, f32)], x: f32) -> (f32, f32) {
+    let n = samples.len() as f32;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f32>() / n;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f32>() / n;
+    let variance = samples
+        .iter()
+        .map(|(x, _)| (x - mean_x).powi(2))
+        .sum::<f32>();
+    let slope = if variance > 0. {
+        samples
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f32>()
+            / variance
+    } else {
+        0.
+    };
+    (mean_y + slope * (x - mean_x), slope)
+}
+
+/// Samples within the last columns before `x` (`direction` -1) or the first columns after it (1)
+fn near(samples: &[(f32, f32)], x: usize, direction: i64) -> Vec<(f32, f32)> {
+    let x = x as f32;
+    let columns = DIRECTION_COLUMNS as f32;
+    samples
+        .iter()
+        .filter(|(sx, _)| {
+            if direction < 0 {
+                *sx <= x && *sx > x - columns
+            } else {
+                *sx >= x && *sx < x + columns
+            }
+        })
+        .copied()
+        .collect()
+}
+
+/// Dashed and dotted curves consist of many short pieces, pieces are chained if the next one
+/// starts within the maximal gap where the direction of the chain predicts it
+/// Gaps which are much longer than the typical gap of a chain are breaks of the curve instead of
+/// gaps between dashes. The remaining gaps are bridged by a cubic through the ends of both sides,
+/// these samples are marked as interpolated.
+pub fn bridge(
+    graphs: Vec<GraphMultiNode>,
+    remaining_verticals: &mut Vec<CombinedVerticals>,
+    settings: &Settings,
+    image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+) -> Vec<GraphMultiNode> {
+    let width = image.width() as usize;
+    let max_gap = settings.step4_max_dash_gap as usize;
+    let max_distance = ((settings.step4_component_jump_height_fraction * image.height() as f32)
+        as u32)
+        .max(2) as f32;
+
+    let mut pieces = graphs
+        .into_iter()
+        .filter_map(Piece::from_graph)
+        .chain(
+            std::mem::take(remaining_verticals)
+                .into_iter()
+                .filter_map(Piece::from_vertical),
+        )
+        .sorted_by_key(|piece| piece.start)
+        .map(Some)
+        .collect_vec();
+
+    let mut chains = Vec::new();
+    while let Some(first) = pieces.iter().position(Option::is_some) {
+        let mut chain = vec![pieces[first].take().unwrap()];
+        let mut chain_samples = chain[0].samples.clone();
+        loop {
+            let last = chain.last().unwrap();
+            let (y_end, slope) = fit(&near(&chain_samples, last.end, -1), last.end as f32);
+            let next = pieces
+                .iter()
+                .enumerate()
+                .filter_map(|(index, piece)| piece.as_ref().map(|piece| (index, piece)))
+                .filter(|(_, piece)| {
+                    piece.start > last.end && piece.start - last.end <= max_gap + 1
+                })
+                .map(|(index, piece)| {
+                    let dx = (piece.start - last.end) as f32;
+                    let error = (y_end + slope * dx - piece.samples[0].1).abs();
+                    (index, error)
+                })
+                .filter(|(_, error)| *error <= max_distance)
+                .min_by(|(_, left), (_, right)| left.total_cmp(right));
+            match next {
+                Some((index, _)) => {
+                    let piece = pieces[index].take().unwrap();
+                    chain_samples.extend_from_slice(&piece.samples);
+                    chain.push(piece);
+                }
+                None => break,
+            }
+        }
+        chains.push(chain);
+    }
+
+    let mut graphs = Vec::new();
+    for chain in chains {
+        // the gaps between dashes of a curve are regular
+        let gaps = chain
+            .iter()
+            .tuple_windows()
+            .map(|(left, right)| right.start - left.end - 1)
+            .sorted()
+            .collect_vec();
+        let max_gap = gaps
+            .get(gaps.len() / 2)
+            .map_or(max_gap, |median| max_gap.min(2 * median + 2));
+
+        let mut parts: Vec<Vec<Piece>> = Vec::new();
+        for piece in chain {
+            match parts.last_mut() {
+                Some(part) if piece.start - part.last().unwrap().end - 1 <= max_gap => {
+                    part.push(piece)
+                }
+                _ => parts.push(vec![piece]),
+            }
+        }
+        for part in parts {
+            // a single dash or dot without neighbors remains as it is
+            if part.len() == 1 && matches!(part[0].source, Source::Vertical(_)) {
+                if let Some(Source::Vertical(vertical)) =
+                    part.into_iter().next().map(|piece| piece.source)
+                {
+                    remaining_verticals.push(vertical);
+                }
+                continue;
+            }
+            let mut graph = GraphMultiNode::empty(width);
+            for piece in part {
+                match piece.source {
+                    Source::Graph(other) => graph.stitch_together(other),
+                    Source::Vertical(vertical) => graph.merge(vertical),
+                }
+            }
+            fill_gaps(&mut graph, max_gap, image.height());
+            graphs.push(graph);
+        }
+    }
+    graphs
+}
+
+/// Cubic hermite interpolation of the missing columns, with the directions of both sides
+fn fill_gaps(graph: &mut GraphMultiNode, max_gap: usize, height: u32) {
+    let samples = samples(graph);
+    for (&(left, _), &(right, _)) in samples.iter().tuple_windows() {
+        let (left, right) = (left as usize, right as usize);
+        let gap = right - left - 1;
+        if gap == 0 || gap > max_gap {
+            continue;
+        }
+        let (y0, m0) = fit(&near(&samples, left, -1), left as f32);
+        let (y1, m1) = fit(&near(&samples, right, 1), right as f32);
+        let length = (right - left) as f32;
+        for x in left + 1..right {
+            let t = (x - left) as f32 / length;
+            let (t2, t3) = (t * t, t * t * t);
+            let y = (2. * t3 - 3. * t2 + 1.) * y0
+                + (t3 - 2. * t2 + t) * length * m0
+                + (-2. * t3 + 3. * t2) * y1
+                + (t3 - t2) * length * m1;
+            graph.ys[x] = MultiNode::interpolated(y, height);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step3_group::{Centroid, VerticalComponentCombined, X};
+
+    /// Dash of one-pixel runs on the line `y = y0 + slope * x`
+    fn dash(columns: std::ops::Range<u32>, y0: f32, slope: f32) -> CombinedVerticals {
+        CombinedVerticals {
+            x_start: X(columns.start),
+            combined: columns
+                .map(|x| {
+                    let y = y0 + slope * x as f32;
+                    let pixel = y.round() as u32;
+                    VerticalComponentCombined {
+                        y_min: pixel,
+                        y_max: pixel,
+                        centroid: Centroid::new(1., y),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn regular_gaps_are_bridged() {
+        let mut remaining = (0..4)
+            .map(|dash_index| dash(dash_index * 10..dash_index * 10 + 6, 20., 0.5))
+            // too far away
+            .chain([dash(80..86, 20., 0.5)])
+            // within the gap but not in the direction of the dashes
+            .chain([dash(40..46, 80., 0.)])
+            .collect_vec();
+        let image = image::GrayImage::new(100, 100);
+        let graphs = bridge(Vec::new(), &mut remaining, &Settings::default(), &image);
+
+        assert_eq!(graphs.len(), 1);
+        let ys = &graphs[0].ys;
+        assert!((0..36).all(|x| ys[x].centroid().is_some()));
+        assert!(ys[36..].iter().all(|ys| ys.centroid().is_none()));
+        assert!(!ys[5].is_interpolated() && !ys[10].is_interpolated());
+        for (x, ys) in ys.iter().enumerate().take(10).skip(6) {
+            assert!(ys.is_interpolated());
+            let y = ys.centroid().unwrap();
+            assert!((y - (20. + 0.5 * x as f32)).abs() < 0.01, "{x} {y}");
+        }
+
+        let starts = remaining.iter().map(|v| v.x_start.0).sorted().collect_vec();
+        assert_eq!(starts, [40, 80]);
+    }
+}
@@ -0,0 +1,183 @@
+use itertools::Itertools;
+
+use crate::step3_group::{GraphMultiNode, MultiNode, VerticalComponent};
+use crate::CurveQuality;
+
+/// Remaining verticals are attached by proximity instead of connectivity
+const REMAINDER_CONFIDENCE: f32 = 0.75;
+/// Bridged gaps of dashed curves are not backed by pixels
+const INTERPOLATED_CONFIDENCE: f32 = 0.5;
+/// Other runs within this multiple of the typical thickness may belong to this curve instead
+const AMBIGUITY_DISTANCE: f32 = 2.;
+const AMBIGUOUS_CONFIDENCE: f32 = 0.5;
+
+fn height(run: &VerticalComponent) -> u32 {
+    run.y_max - run.y_min + 1
+}
+
+fn distance(run: &VerticalComponent, y: f32) -> f32 {
+    (run.y_min as f32 - y).max(y - run.y_max as f32).max(0.)
+}
+
+/// Median height of the columns with a single run, where the curve is neither steep nor crossed
+fn typical_thickness(graph: &GraphMultiNode) -> f32 {
+    let heights = graph
+        .ys
+        .iter()
+        .filter(|ys| !ys.is_interpolated())
+        .map(MultiNode::runs)
+        .filter(|runs| runs.len() == 1)
+        .map(|runs| height(&runs[0]))
+        .sorted()
+        .collect_vec();
+    heights.get(heights.len() / 2).copied().unwrap_or(1) as f32
+}
+
+/// Confidence between 0 and 1 of each sample of the graph, it is lowered if
+/// - the run is thicker than usual, e.g. where curves cross or the curve is steep
+/// - the pixels were a remaining vertical or a bridged gap instead of a large component
+/// - other runs of this or another graph are close-by
+pub fn confidence(
+    graph: &GraphMultiNode,
+    all_graphs: &[&GraphMultiNode],
+    samples: &[(f32, f32)],
+) -> Vec<f32> {
+    let typical = typical_thickness(graph);
+    let width = graph.ys.len();
+    samples
+        .iter()
+        .map(|&(x, y)| {
+            let x = (x.max(0.) as usize).min(width.saturating_sub(1));
+            let Some(ys) = graph.ys.get(x) else {
+                return 0.;
+            };
+            let runs = ys.runs();
+            let nearest = runs
+                .iter()
+                .position_min_by(|left, right| distance(left, y).total_cmp(&distance(right, y)));
+            let (source, thickness) = if ys.is_interpolated() {
+                (INTERPOLATED_CONFIDENCE, 1.)
+            } else {
+                let thickness = nearest.map_or(1., |nearest| {
+                    (typical / height(&runs[nearest]) as f32).min(1.)
+                });
+                let source = if ys.is_remainder() {
+                    REMAINDER_CONFIDENCE
+                } else {
+                    1.
+                };
+                (source, thickness)
+            };
+            let max_distance = AMBIGUITY_DISTANCE * typical;
+            let other_runs = runs
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| Some(*index) != nearest)
+                .map(|(_, run)| run.clone())
+                .chain(
+                    all_graphs
+                        .iter()
+                        .filter(|other| !std::ptr::eq(**other, graph))
+                        .filter_map(|other| other.ys.get(x))
+                        .filter(|ys| !ys.is_interpolated())
+                        .flat_map(MultiNode::runs),
+                )
+                .collect_vec();
+            let ambiguity = if other_runs
+                .iter()
+                .any(|run| distance(run, y) <= max_distance)
+            {
+                AMBIGUOUS_CONFIDENCE
+            } else {
+                1.
+            };
+            source * thickness * ambiguity
+        })
+        .collect()
+}
+
+/// Columns backed by pixels between the first and the last column of the graph
+pub fn quality(graph: &GraphMultiNode, confidence: &[f32]) -> CurveQuality {
+    let columns = graph.ys.iter().map(|ys| ys.mean().is_some()).collect_vec();
+    let span = match (
+        columns.iter().position(|c| *c),
+        columns.iter().rposition(|c| *c),
+    ) {
+        (Some(first), Some(last)) => last - first + 1,
+        _ => 0,
+    };
+    let detected = graph
+        .ys
+        .iter()
+        .filter(|ys| ys.mean().is_some() && !ys.is_interpolated())
+        .count();
+    CurveQuality {
+        coverage: if span > 0 {
+            detected as f32 / span as f32
+        } else {
+            0.
+        },
+        confidence: if confidence.is_empty() {
+            0.
+        } else {
+            confidence.iter().sum::<f32>() / confidence.len() as f32
+        },
+    }
+}
+
+/// Red for a low and green for a high confidence
+pub fn color(confidence: f32) -> image::Rgba<u8> {
+    let confidence = confidence.clamp(0., 1.);
+    let red = (2. * (1. - confidence)).min(1.) * 255.;
+    let green = (2. * confidence).min(1.) * 255.;
+    image::Rgba([red as u8, green as u8, 0, 255])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step3_group::{Centroid, CombinedVerticals, VerticalComponentCombined, X};
+
+    fn run(y_min: u32, y_max: u32) -> MultiNode {
+        MultiNode::from_run(y_min, y_max, Centroid::new(1., (y_min + y_max) as f32 / 2.))
+    }
+
+    #[test]
+    fn confidence_is_lowered_where_the_curve_is_uncertain() {
+        let mut graph = GraphMultiNode::empty(10);
+        for x in 0..9 {
+            graph.ys[x] = run(20, 21);
+        }
+        graph.ys[3] = run(19, 22);
+        graph.ys[5] = MultiNode::interpolated(20.5, 100);
+        graph.merge(CombinedVerticals {
+            x_start: X(9),
+            combined: vec![VerticalComponentCombined {
+                y_min: 20,
+                y_max: 21,
+                centroid: Centroid::new(2., 20.5),
+            }],
+        });
+        let mut other = GraphMultiNode::empty(10);
+        other.ys[7] = run(23, 24);
+        other.ys[8] = run(50, 51);
+
+        let samples = (0..10).map(|x| (x as f32, 20.5)).collect_vec();
+        let confidence = confidence(&graph, &[&graph, &other], &samples);
+        assert_eq!(
+            confidence,
+            [1., 1., 1., 0.5, 1., 0.5, 1., 0.5, 1., REMAINDER_CONFIDENCE]
+        );
+
+        let quality = quality(&graph, &confidence);
+        assert_eq!(quality.coverage, 0.9);
+        assert_eq!(quality.confidence, 8.25 / 10.);
+    }
+
+    #[test]
+    fn colors_range_from_red_to_green() {
+        assert_eq!(color(0.), image::Rgba([255, 0, 0, 255]));
+        assert_eq!(color(0.5), image::Rgba([255, 255, 0, 255]));
+        assert_eq!(color(2.), image::Rgba([0, 255, 0, 255]));
+    }
+}
@@ -0,0 +1,54 @@
+use itertools::Itertools;
+
+use crate::step3_group::GraphMultiNode;
+use crate::LineStyle;
+
+/// Segments up to this length are dots instead of dashes
+const MAX_DOT_LENGTH: usize = 4;
+/// Solid curves may have a few gaps, e.g. where another curve crosses
+const MIN_SOLID_COVERAGE: f32 = 0.9;
+/// Steep parts of a curve leave single columns out, these are not gaps of the line style
+const MAX_STEEP_GAP: usize = 2;
+
+/// Classifies a graph by the columns covered between its first and last column
+pub fn line_style(graph: &GraphMultiNode) -> LineStyle {
+    let covered = graph
+        .ys
+        .iter()
+        .map(|ys| ys.mean().is_some() && !ys.is_interpolated())
+        .skip_while(|covered| !covered)
+        .collect_vec();
+    let Some(last) = covered.iter().rposition(|covered| *covered) else {
+        return LineStyle::Solid;
+    };
+    let mut covered = covered;
+    let mut gap_start = None;
+    for x in 0..=last {
+        match (covered[x], gap_start) {
+            (false, None) => gap_start = Some(x),
+            (true, Some(start)) => {
+                if x - start <= MAX_STEEP_GAP {
+                    covered[start..x].fill(true);
+                }
+                gap_start = None;
+            }
+            _ => {}
+        }
+    }
+    let segments = covered[..=last]
+        .iter()
+        .chunk_by(|covered| **covered)
+        .into_iter()
+        .filter(|(covered, _)| *covered)
+        .map(|(_, segment)| segment.count())
+        .sorted()
+        .collect_vec();
+    let coverage = segments.iter().sum::<usize>() as f32 / (last + 1) as f32;
+    if segments.len() == 1 || coverage >= MIN_SOLID_COVERAGE {
+        LineStyle::Solid
+    } else if segments[segments.len() / 2] <= MAX_DOT_LENGTH {
+        LineStyle::Dotted
+    } else {
+        LineStyle::Dashed
+    }
+}
@@ -0,0 +1,166 @@
+use itertools::Itertools;
+
+use crate::step3_group::{GraphMultiNode, VerticalComponent};
+
+fn touches(a: &VerticalComponent, b: &VerticalComponent) -> bool {
+    a.y_min <= b.y_max.saturating_add(1) && b.y_min <= a.y_max.saturating_add(1)
+}
+
+/// Branches of a multi-valued curve overlap in x and are therefore separate graphs,
+/// graphs whose runs touch are combined so that the polyline can follow the whole curve
+pub fn connect_touching(graphs: Vec<GraphMultiNode>) -> Vec<GraphMultiNode> {
+    let mut connected: Vec<GraphMultiNode> = Vec::new();
+    for mut graph in graphs {
+        while let Some(index) = connected
+            .iter()
+            .position(|other| graph_touches(&graph, other))
+        {
+            graph.aggregate(connected.remove(index));
+        }
+        connected.push(graph);
+    }
+    connected
+}
+
+fn graph_touches(left: &GraphMultiNode, right: &GraphMultiNode) -> bool {
+    let right = right.ys.iter().map(|ys| ys.runs()).collect_vec();
+    left.ys.iter().enumerate().any(|(x, ys)| {
+        let runs = ys.runs();
+        (x.saturating_sub(1)..=x + 1)
+            .filter_map(|x| right.get(x))
+            .any(|other| {
+                runs.iter()
+                    .any(|run| other.iter().any(|other| touches(run, other)))
+            })
+    })
+}
+
+/// Orders every distinct vertical run of a graph along the curve, returns the run centroids
+/// Runs in neighboring columns are connected if they touch. The walk keeps its horizontal direction
+/// as long as possible, turns around at the end of a branch and jumps to the closest remaining run
+/// if it gets stuck.
+pub fn polyline(graph: &GraphMultiNode) -> Vec<(f32, f32)> {
+    let columns = graph.ys.iter().map(|ys| ys.runs()).collect_vec();
+    let mut visited = columns
+        .iter()
+        .map(|runs| vec![false; runs.len()])
+        .collect_vec();
+    let center = |(x, index): (usize, usize)| {
+        let run: &VerticalComponent = &columns[x][index];
+        let y = run
+            .centroid
+            .y()
+            .unwrap_or((run.y_min + run.y_max) as f32 / 2.);
+        (x as f32, y)
+    };
+
+    let Some(mut current) = columns
+        .iter()
+        .position(|runs| !runs.is_empty())
+        .map(|x| (x, 0))
+    else {
+        return Vec::new();
+    };
+    let mut direction = 1;
+    let mut points = Vec::new();
+    loop {
+        visited[current.0][current.1] = true;
+        let (x, y) = center(current);
+        points.push((x, y));
+
+        let run = &columns[current.0][current.1];
+        let next = [direction, -direction].into_iter().find_map(|dx| {
+            let next_x = current.0.checked_add_signed(dx)?;
+            let runs = columns.get(next_x)?;
+            runs.iter()
+                .enumerate()
+                .filter(|(index, next_run)| !visited[next_x][*index] && touches(run, next_run))
+                .min_by(|(left, _), (right, _)| {
+                    let dy = |index: usize| (center((next_x, index)).1 - y).abs();
+                    dy(*left).total_cmp(&dy(*right))
+                })
+                .map(|(index, _)| (dx, (next_x, index)))
+        });
+        if let Some((dx, next)) = next {
+            direction = dx;
+            current = next;
+            continue;
+        }
+        let closest = visited
+            .iter()
+            .enumerate()
+            .flat_map(|(x, visited)| {
+                visited
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, visited)| !**visited)
+                    .map(move |(index, _)| (x, index))
+            })
+            .min_by(|&left, &right| {
+                let distance = |next| {
+                    let (next_x, next_y) = center(next);
+                    (next_x - x).powi(2) + (next_y - y).powi(2)
+                };
+                distance(left).total_cmp(&distance(right))
+            });
+        match closest {
+            Some(next) => {
+                direction = if next.0 < current.0 { -1 } else { 1 };
+                current = next;
+            }
+            None => break,
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step3_group::{Centroid, MultiNode};
+
+    /// Graph with one-pixel runs at the given x and y
+    fn graph(pixels: impl IntoIterator<Item = (usize, u32)>) -> GraphMultiNode {
+        let mut graph = GraphMultiNode::empty(10);
+        for (x, y) in pixels {
+            let mut pixel = GraphMultiNode::empty(10);
+            pixel.ys[x] = MultiNode::from_run(y, y, Centroid::new(1., y as f32));
+            graph.aggregate(pixel);
+        }
+        graph
+    }
+
+    /// Branches of a '>' which meet at x = 5
+    fn upper() -> impl Iterator<Item = (usize, u32)> {
+        (0..=5).map(|x| (x, 5 + x as u32))
+    }
+    fn lower() -> impl Iterator<Item = (usize, u32)> {
+        (0..5).map(|x| (x, 15 - x as u32))
+    }
+
+    #[test]
+    fn walk_turns_around_at_the_end_of_a_branch() {
+        let points = polyline(&graph(upper().chain(lower())));
+        let expected = upper()
+            .chain(lower().collect_vec().into_iter().rev())
+            .map(|(x, y)| (x as f32, y as f32))
+            .collect_vec();
+        assert_eq!(points, expected);
+    }
+
+    #[test]
+    fn walk_jumps_over_gaps() {
+        let points = polyline(&graph([(0, 5), (1, 5), (4, 8), (5, 8)]));
+        assert_eq!(points, [(0., 5.), (1., 5.), (4., 8.), (5., 8.)]);
+        assert!(polyline(&GraphMultiNode::empty(10)).is_empty());
+    }
+
+    #[test]
+    fn touching_branches_are_connected() {
+        let far = graph([(2, 30), (3, 30)]);
+        let connected = connect_touching(vec![graph(upper()), far, graph(lower())]);
+        assert_eq!(connected.len(), 2);
+        assert_eq!(polyline(&connected[0]), [(2., 30.), (3., 30.)]);
+        assert_eq!(polyline(&connected[1]).len(), 11);
+    }
+}
@@ -0,0 +1,219 @@
+use itertools::Itertools;
+
+use crate::Smoothing;
+
+/// Deviations from the median beyond this many standard deviations are spikes
+const SPIKE_THRESHOLD: f64 = 3.;
+/// Deviations up to one pixel are no spikes, e.g. at steps of the curve where the median absolute
+/// deviation is 0
+const MIN_SPIKE: f64 = 1.;
+/// Scales the median absolute deviation to the standard deviation of a normal distribution
+const MAD_TO_STANDARD_DEVIATION: f64 = 1.4826;
+/// Steps beyond this multiple of the median step are gaps of the curve, filters are not applied
+/// across them
+const GAP_FACTOR: f64 = 3.;
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
+
+/// Half of the window, windows are centered and shrink symmetrically at both ends
+fn half_width(window: u32) -> usize {
+    (window as usize).saturating_sub(1) / 2
+}
+
+fn centered(values: &[f64], index: usize, half: usize) -> &[f64] {
+    let half = half.min(index).min(values.len() - 1 - index);
+    &values[index - half..=index + half]
+}
+
+/// Despiking and smoothing of each part of the curve between gaps, in pixels of the crop
+/// Polylines are filtered in both coordinates, functions only in y.
+pub fn filter(points: &[(f64, f64)], both: bool, settings: &crate::Settings) -> Vec<(f64, f64)> {
+    let step = |(left, right): (&(f64, f64), &(f64, f64))| {
+        if both {
+            (right.0 - left.0).hypot(right.1 - left.1)
+        } else {
+            (right.0 - left.0).abs()
+        }
+    };
+    let steps = points.iter().tuple_windows().map(step).collect_vec();
+    let median_step = if steps.is_empty() {
+        0.
+    } else {
+        median(&mut steps.clone())
+    };
+    let mut filtered = Vec::with_capacity(points.len());
+    let mut start = 0;
+    for end in 1..=points.len() {
+        if end < points.len() && steps[end - 1] <= GAP_FACTOR * median_step {
+            continue;
+        }
+        let part = &points[start..end];
+        let mut xs = part.iter().map(|(x, _)| *x).collect_vec();
+        let mut ys = part.iter().map(|(_, y)| *y).collect_vec();
+        for values in if both {
+            vec![&mut xs, &mut ys]
+        } else {
+            vec![&mut ys]
+        } {
+            if settings.step6_despike_window > 1 {
+                *values = despike(values, settings.step6_despike_window);
+            }
+            *values = smooth(
+                values,
+                settings.step6_smoothing,
+                settings.step6_smoothing_window,
+            );
+        }
+        filtered.extend(xs.into_iter().zip(ys));
+        start = end;
+    }
+    filtered
+}
+
+/// Hampel filter, values far from the median of their window are replaced by the median
+fn despike(values: &[f64], window: u32) -> Vec<f64> {
+    let half = half_width(window);
+    (0..values.len())
+        .map(|index| {
+            let mut neighbors = centered(values, index, half).to_vec();
+            let center = median(&mut neighbors);
+            let mut deviations = neighbors.iter().map(|v| (v - center).abs()).collect_vec();
+            let threshold = SPIKE_THRESHOLD * MAD_TO_STANDARD_DEVIATION * median(&mut deviations);
+            if (values[index] - center).abs() > threshold.max(MIN_SPIKE) {
+                center
+            } else {
+                values[index]
+            }
+        })
+        .collect()
+}
+
+/// Weights of the quadratic Savitzky-Golay filter for `half` points on each side
+fn savitzky_golay_weights(half: usize) -> Vec<f64> {
+    let m = half as f64;
+    let norm = (4. * m * m - 1.) * (2. * m + 3.);
+    (-(half as i64)..=half as i64)
+        .map(|i| 3. * (3. * m * m + 3. * m - 1. - 5. * (i * i) as f64) / norm)
+        .collect()
+}
+
+/// The samples are assumed to be evenly spaced, e.g. one per pixel column
+fn smooth(values: &[f64], smoothing: Smoothing, window: u32) -> Vec<f64> {
+    let half = half_width(window);
+    (0..values.len())
+        .map(|index| {
+            let neighbors = centered(values, index, half);
+            match smoothing {
+                Smoothing::None => values[index],
+                Smoothing::MovingAverage => neighbors.iter().sum::<f64>() / neighbors.len() as f64,
+                // a quadratic fit needs at least two points on each side
+                Smoothing::SavitzkyGolay if neighbors.len() < 5 => values[index],
+                Smoothing::SavitzkyGolay => savitzky_golay_weights(neighbors.len() / 2)
+                    .iter()
+                    .zip(neighbors)
+                    .map(|(weight, value)| weight * value)
+                    .sum(),
+            }
+        })
+        .collect()
+}
+
+fn distance_to_segment(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0. {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (point.0 - start.0 - t * dx).hypot(point.1 - start.1 - t * dy)
+}
+
+/// Ramer-Douglas-Peucker, returns the indices of the remaining points
+/// Points which must be kept, e.g. with an error bar, split the curve into parts which are
+/// simplified separately.
+pub fn simplify(points: &[(f64, f64)], tolerance: f64, keep: &[bool]) -> Vec<usize> {
+    assert_eq!(keep.len(), points.len());
+    if points.len() < 3 {
+        return (0..points.len()).collect();
+    }
+    let mut kept = keep.to_vec();
+    kept[0] = true;
+    kept[points.len() - 1] = true;
+    let fixed = kept.iter().positions(|k| *k).collect_vec();
+    let mut stack = fixed.into_iter().tuple_windows().collect_vec();
+    while let Some((start, end)) = stack.pop() {
+        let farthest = (start + 1..end)
+            .map(|index| {
+                let distance = distance_to_segment(points[index], points[start], points[end]);
+                (index, distance)
+            })
+            .max_by(|(_, left), (_, right)| left.total_cmp(right));
+        if let Some((index, distance)) = farthest {
+            if distance > tolerance {
+                kept[index] = true;
+                stack.push((start, index));
+                stack.push((index, end));
+            }
+        }
+    }
+    kept.iter().positions(|k| *k).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn despike_replaces_spikes_by_the_median() {
+        let values = [0., 0.5, 0., 10., 0., -0.5, 0.];
+        assert_eq!(despike(&values, 5), [0., 0.5, 0., 0., 0., -0.5, 0.]);
+    }
+
+    #[test]
+    fn despike_keeps_steps_of_one_pixel() {
+        // the median absolute deviation around the step is 0
+        let values = [0., 0., 0., 0., 1., 1., 0., 0., 0., 0.];
+        assert_eq!(despike(&values, 7), values);
+        let stairs = [0., 0., 1., 1., 2., 2., 3., 3.];
+        assert_eq!(despike(&stairs, 7), stairs);
+    }
+
+    #[test]
+    fn smoothing_keeps_lines_and_parabolas() {
+        let line = (0..10).map(|x| 2. * x as f64 + 1.).collect_vec();
+        assert_eq!(smooth(&line, Smoothing::MovingAverage, 3), line);
+        let parabola = (0..10).map(|x| (x * x) as f64).collect_vec();
+        for (smoothed, value) in smooth(&parabola, Smoothing::SavitzkyGolay, 5)
+            .iter()
+            .zip(&parabola)
+        {
+            assert!((smoothed - value).abs() < 1e-9, "{smoothed} {value}");
+        }
+        assert_eq!(smooth(&parabola, Smoothing::None, 5), parabola);
+    }
+
+    #[test]
+    fn smoothing_averages_noise() {
+        let noisy = [0., 1., 0., 1., 0., 1., 0.];
+        let smoothed = smooth(&noisy, Smoothing::MovingAverage, 3);
+        assert_eq!(smoothed[0], 0.);
+        assert_eq!(smoothed[2], 2. / 3.);
+        assert_eq!(smoothed[3], 1. / 3.);
+    }
+
+    #[test]
+    fn simplify_keeps_corners_and_required_points() {
+        let points = (0..10)
+            .map(|x| (x as f64, if x < 5 { 0. } else { (x - 5) as f64 }))
+            .collect_vec();
+        assert_eq!(simplify(&points, 0.1, &[false; 10]), [0, 5, 9]);
+        let mut keep = [false; 10];
+        keep[2] = true;
+        assert_eq!(simplify(&points, 0.1, &keep), [0, 2, 5, 9]);
+        assert_eq!(simplify(&points, 10., &[false; 10]), [0, 9]);
+    }
+}
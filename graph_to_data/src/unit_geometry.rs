@@ -17,13 +17,6 @@ impl UnitInterval {
         (self.0 * size as f32) as u32
     }
 }
-impl UnitInterval {
-    #[inline(always)]
-    fn interpolate(min: Self, max: Self, delta: f32) -> Self {
-        let target = max.0 * delta + (1. - delta) * min.0;
-        Self(target.clamp(0., 1.))
-    }
-}
 impl Eq for UnitInterval {}
 #[allow(clippy::derive_ord_xor_partial_ord)]
 impl Ord for UnitInterval {
@@ -52,13 +45,6 @@ impl UnitPoint {
             None
         }
     }
-    #[inline(always)]
-    pub(super) fn interpolate(min: Self, max: Self, steps: u32, target: u32) -> Self {
-        let delta = target as f32 / (steps - 1) as f32;
-        let x = UnitInterval::interpolate(min.x, max.x, delta);
-        let y = UnitInterval::interpolate(min.y, max.y, delta);
-        Self { x, y }
-    }
 
     #[must_use]
     fn transform(&self, [width, height]: [u32; 2]) -> (u32, u32) {
@@ -112,6 +98,39 @@ impl UnitQuadrilateral {
         )
     }
 
+    /// Corners in clockwise order (for the y-axis pointing downwards)
+    fn corners(&self) -> [[f32; 2]; 4] {
+        let Self { lt, lb, rt, rb } = self;
+        [lt, rt, rb, lb].map(|p| [p.x.0, p.y.0])
+    }
+
+    /// Fails for collapsed corners/edges and for crossing edges
+    pub fn check(&self) -> Result<(), crate::Error> {
+        if self.turns().iter().any(|turn| turn.abs() <= f32::EPSILON) {
+            return Err(crate::Error::QuadrilateralDegenerate);
+        }
+        let crossing = |[a, b]: [[f32; 2]; 2], [c, d]: [[f32; 2]; 2]| {
+            orientation(a, b, c).signum() != orientation(a, b, d).signum()
+                && orientation(c, d, a).signum() != orientation(c, d, b).signum()
+        };
+        let [lt, rt, rb, lb] = self.corners();
+        if crossing([lt, rt], [rb, lb]) || crossing([rt, rb], [lb, lt]) {
+            return Err(crate::Error::QuadrilateralSelfIntersecting);
+        }
+        Ok(())
+    }
+
+    pub fn is_convex(&self) -> bool {
+        let turns = self.turns();
+        turns.iter().all(|t| *t > 0.) || turns.iter().all(|t| *t < 0.)
+    }
+
+    /// Orientation of each triple of consecutive corners
+    fn turns(&self) -> [f32; 4] {
+        let corners = self.corners();
+        [0, 1, 2, 3].map(|i| orientation(corners[i], corners[(i + 1) % 4], corners[(i + 2) % 4]))
+    }
+
     #[must_use]
     pub fn transform(&self, size: [u32; 2]) -> QuadrilateralU32 {
         let Self { lt, lb, rt, rb } = self;
@@ -122,6 +141,9 @@ impl UnitQuadrilateral {
         QuadrilateralU32 { lt, lb, rt, rb }
     }
 }
+fn orientation(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
 pub struct QuadrilateralU32 {
     lt: (u32, u32),
     lb: (u32, u32),
@@ -129,11 +151,15 @@ pub struct QuadrilateralU32 {
     rb: (u32, u32),
 }
 impl QuadrilateralU32 {
+    /// Of the bounding box, the corners may be mirrored
     pub fn width(&self) -> u32 {
-        self.rt.0.max(self.rb.0) - self.lt.0.min(self.lb.0)
+        let xs = [self.lt.0, self.lb.0, self.rt.0, self.rb.0];
+        xs.iter().max().unwrap_or(&0) - xs.iter().min().unwrap_or(&0)
     }
 
+    /// Of the bounding box, the corners may be mirrored
     pub fn height(&self) -> u32 {
-        self.lb.1.max(self.rb.1) - self.lt.1.min(self.rt.1)
+        let ys = [self.lt.1, self.lb.1, self.rt.1, self.rb.1];
+        ys.iter().max().unwrap_or(&0) - ys.iter().min().unwrap_or(&0)
     }
 }
@@ -1,14 +1,16 @@
 use graph_to_data::{UnitInterval, UnitPoint, UnitQuadrilateral};
 
 mod axis_settings;
+mod calibration_settings;
 mod crop_settings;
 mod file_loading;
+mod polar_settings;
 
 use super::ImageBuf;
 use axis_settings::AxisSettings;
+use calibration_settings::{CalibrationSettings, ReferenceAxis};
 use crop_settings::CropSettings;
-
-pub use axis_settings::Axes;
+use polar_settings::{PolarPoint, PolarSettings};
 
 #[derive(Default, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
@@ -26,6 +28,10 @@ pub struct Tab {
     settings_as_string: Option<SettingsAsString>,
     crop_settings: CropSettings,
     axis_settings: AxisSettings,
+    calibration_settings: CalibrationSettings,
+    polar_settings: PolarSettings,
+    /// The intermediate images are only computed and shipped while the inspector is open
+    inspect_pipeline: bool,
 }
 
 struct DetectionTaskWrapper {
@@ -41,7 +47,6 @@ impl Default for DetectionTaskWrapper {
 
 #[derive(Default, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
-
 struct ParseableTextBox {
     before: String,
     current: String,
@@ -55,11 +60,30 @@ impl Parseable for u8 {
         s.parse().map_err(|e| format!("{e:?}"))
     }
 }
+impl Parseable for u32 {
+    fn parse(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|e| format!("{e:?}"))
+    }
+}
 impl Parseable for f32 {
     fn parse(s: &str) -> Result<Self, String> {
         s.parse().map_err(|e| format!("{e:?}"))
     }
 }
+impl Parseable for f64 {
+    fn parse(s: &str) -> Result<Self, String> {
+        s.parse().map_err(|e| format!("{e:?}"))
+    }
+}
+/// Values separated by commas, semicolons or whitespace
+impl Parseable for Vec<f64> {
+    fn parse(s: &str) -> Result<Self, String> {
+        s.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+            .map(f64::parse)
+            .collect()
+    }
+}
 impl ParseableTextBox {
     fn show_and_parse<T: Parseable>(
         &mut self,
@@ -109,16 +133,35 @@ impl ParseableTextBox {
 #[derive(Default, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
 struct SettingsAsString {
+    step0_gridline_min_length_fraction: ParseableTextBox,
     step1_width_minimial_fraction: ParseableTextBox,
     step1_height_maximal_fraction: ParseableTextBox,
     step1_close_count: ParseableTextBox,
     step1_step2_color_radius: ParseableTextBox,
+    step2_frame_min_length_fraction: ParseableTextBox,
+    step2_max_symbol_size: ParseableTextBox,
     step3_min_width_fraction: ParseableTextBox,
+    step3_marker_min_pixels: ParseableTextBox,
+    step3_marker_max_size_fraction: ParseableTextBox,
+    step3_marker_min_count: ParseableTextBox,
+    step3_bar_min_width: ParseableTextBox,
+    step3_error_bar_max_width: ParseableTextBox,
+    step3_error_bar_min_length: ParseableTextBox,
     step4_component_jump_height_fraction: ParseableTextBox,
+    step4_max_dash_gap: ParseableTextBox,
+    step6_despike_window: ParseableTextBox,
+    step6_smoothing_window: ParseableTextBox,
+    step6_simplify_tolerance: ParseableTextBox,
+    step6_resample_count: ParseableTextBox,
+    step6_resample_step: ParseableTextBox,
+    step6_resample_xs: ParseableTextBox,
 }
 impl SettingsAsString {
     fn new(settings: &graph_to_data::Settings) -> Self {
         Self {
+            step0_gridline_min_length_fraction: ParseableTextBox::new(
+                settings.step0_gridline_min_length_fraction,
+            ),
             step1_width_minimial_fraction: ParseableTextBox::new(
                 settings.step1_width_minimial_fraction,
             ),
@@ -126,10 +169,40 @@ impl SettingsAsString {
                 settings.step1_height_maximal_fraction,
             ),
             step1_step2_color_radius: ParseableTextBox::new(settings.step1_step2_color_radius),
+            step2_frame_min_length_fraction: ParseableTextBox::new(
+                settings.step2_frame_min_length_fraction,
+            ),
+            step2_max_symbol_size: ParseableTextBox::new(settings.step2_max_symbol_size),
             step3_min_width_fraction: ParseableTextBox::new(settings.step3_min_width_fraction),
+            step3_marker_min_pixels: ParseableTextBox::new(settings.step3_marker_min_pixels),
+            step3_marker_max_size_fraction: ParseableTextBox::new(
+                settings.step3_marker_max_size_fraction,
+            ),
+            step3_marker_min_count: ParseableTextBox::new(settings.step3_marker_min_count),
+            step3_bar_min_width: ParseableTextBox::new(settings.step3_bar_min_width),
+            step3_error_bar_max_width: ParseableTextBox::new(settings.step3_error_bar_max_width),
+            step3_error_bar_min_length: ParseableTextBox::new(settings.step3_error_bar_min_length),
             step4_component_jump_height_fraction: ParseableTextBox::new(
                 settings.step4_component_jump_height_fraction,
             ),
+            step4_max_dash_gap: ParseableTextBox::new(settings.step4_max_dash_gap),
+            step6_despike_window: ParseableTextBox::new(settings.step6_despike_window),
+            step6_smoothing_window: ParseableTextBox::new(settings.step6_smoothing_window),
+            step6_simplify_tolerance: ParseableTextBox::new(settings.step6_simplify_tolerance),
+            step6_resample_count: ParseableTextBox::new(match settings.step6_resample_grid {
+                graph_to_data::ResampleGrid::Count(count) => count,
+                _ => 100,
+            }),
+            step6_resample_step: ParseableTextBox::new(match settings.step6_resample_grid {
+                graph_to_data::ResampleGrid::Step(step) => step,
+                _ => 1.,
+            }),
+            step6_resample_xs: ParseableTextBox::new(match &settings.step6_resample_grid {
+                graph_to_data::ResampleGrid::Explicit(xs) => {
+                    xs.iter().map(f64::to_string).collect::<Vec<_>>().join(", ")
+                }
+                _ => String::new(),
+            }),
             step1_close_count: ParseableTextBox::new(settings.step1_close_count),
         }
     }
@@ -160,22 +233,58 @@ impl Tab {
             ui.set_enabled(false);
         }
         if let Some(result) = self.detection_task.task.check() {
-            let result = match result {
-                Ok((Some(image), csv)) => {
-                    let image: crate::ImageBuf = image.into();
-                    let size = [image.width() as _, image.height() as _];
-                    let pixels = image.as_flat_samples();
-                    let egui_image =
-                        egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-                    let id = format!("ID: {:?}", ui.auto_id_with("Image"));
-                    let image =
-                        ui.ctx()
-                            .load_texture(id, egui_image, egui::TextureOptions::NEAREST);
-                    Ok((Some(image), csv))
-                }
-                Ok((None, csv)) => Ok((None, csv)),
-                Err(e) => Err(e),
+            let load_texture = |image: crate::tasks::ImageSerde, name: &str| {
+                let image: crate::ImageBuf = image.try_into().ok()?;
+                let size = [image.width() as _, image.height() as _];
+                let pixels = image.as_flat_samples();
+                let egui_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
+                let id = format!("ID: {:?}", ui.auto_id_with(name));
+                Some(
+                    ui.ctx()
+                        .load_texture(id, egui_image, egui::TextureOptions::NEAREST),
+                )
             };
+            let result = result.map(|output| {
+                let crate::tasks::DetectionTaskOutput {
+                    image,
+                    confidence_image,
+                    detected,
+                    mode,
+                    names,
+                    stages,
+                } = output;
+                let names = names
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (name, label))| PlotName {
+                        name,
+                        label: label
+                            .and_then(|label| load_texture(label, &format!("Label {index}"))),
+                    })
+                    .collect();
+                let stages = stages
+                    .into_iter()
+                    .enumerate()
+                    .map(
+                        |(index, crate::tasks::StageImage { name, color, image })| Stage {
+                            name,
+                            color,
+                            texture: load_texture(image, &format!("Stage {index}")),
+                        },
+                    )
+                    .collect();
+                Detected {
+                    image: image.and_then(|image| load_texture(image, "Image")),
+                    confidence_image: confidence_image
+                        .and_then(|image| load_texture(image, "Confidence image")),
+                    show_confidence: false,
+                    detected,
+                    mode,
+                    names,
+                    stages,
+                    inspected: None,
+                }
+            });
             self.state = State::LineDetected(Box::new(result));
         }
 
@@ -274,21 +383,162 @@ impl Tab {
                         self.settings_as_string = Some(SettingsAsString::new(&self.settings));
                     }
                     let SettingsAsString {
+                        step0_gridline_min_length_fraction,
                         step1_width_minimial_fraction,
                         step1_height_maximal_fraction,
                         step1_close_count,
                         step1_step2_color_radius,
+                        step2_frame_min_length_fraction,
+                        step2_max_symbol_size,
                         step3_min_width_fraction,
+                        step3_marker_min_pixels,
+                        step3_marker_max_size_fraction,
+                        step3_marker_min_count,
+                        step3_bar_min_width,
+                        step3_error_bar_max_width,
+                        step3_error_bar_min_length,
                         step4_component_jump_height_fraction,
+                        step4_max_dash_gap,
+                        step6_despike_window,
+                        step6_smoothing_window,
+                        step6_simplify_tolerance,
+                        step6_resample_count,
+                        step6_resample_step,
+                        step6_resample_xs,
                     } = &mut self.settings_as_string.as_mut().unwrap();
                     egui::Grid::new("detection_settings_grid")
                         .num_columns(2)
                         .show(ui, |ui| {
+                            {
+                                ui.label("Mode").on_hover_text(
+                                    "Lines: continuous curves\n\
+                                    Scatter: isolated markers\n\
+                                    Bars: filled rectangles",
+                                );
+                                egui::ComboBox::from_id_source("detection_mode")
+                                    .selected_text(format!("{:?}", self.settings.mode))
+                                    .show_ui(ui, |ui| {
+                                        for mode in [
+                                            graph_to_data::DetectionMode::Lines,
+                                            graph_to_data::DetectionMode::Scatter,
+                                            graph_to_data::DetectionMode::Bars,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut self.settings.mode,
+                                                mode,
+                                                format!("{mode:?}"),
+                                            );
+                                        }
+                                    });
+                            }
+                            ui.end_row();
+                            {
+                                ui.label("Step 0: Crop mode").on_hover_text(
+                                    "Bilinear: blend the edges of the crop area\n\
+                                    Homography: perspective correction, \
+                                    e.g. for photos of printed plots",
+                                );
+                                egui::ComboBox::from_id_source("crop_mode")
+                                    .selected_text(format!("{:?}", self.settings.step0_crop_mode))
+                                    .show_ui(ui, |ui| {
+                                        for mode in [
+                                            graph_to_data::CropMode::Bilinear,
+                                            graph_to_data::CropMode::Homography,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut self.settings.step0_crop_mode,
+                                                mode,
+                                                format!("{mode:?}"),
+                                            );
+                                        }
+                                    });
+                            }
+                            ui.end_row();
+                            {
+                                ui.label("Step 0: Remove gridlines").on_hover_text(
+                                    "Replace long horizontal and vertical lines, \
+                                    e.g. gridlines and the axis frame, by the background\n\
+                                    Lines in the color of a curve are kept",
+                                );
+                                ui.checkbox(&mut self.settings.step0_remove_gridlines, "");
+                            }
+                            ui.end_row();
+                            if self.settings.step0_remove_gridlines {
+                                step0_gridline_min_length_fraction.show_and_parse(
+                                    "Step 0: Gridline min length",
+                                    "Minimal length of a gridline\n\
+                                    Fraction of the image width or height, \
+                                    value between 0.0 and 1.0",
+                                    &mut self.settings.step0_gridline_min_length_fraction,
+                                    ui,
+                                );
+                                ui.end_row();
+                            }
                             {
                                 ui.label("Step 1: ignore gray");
                                 ui.checkbox(&mut self.settings.step1_ignore_gray, "");
                             }
                             ui.end_row();
+                            {
+                                ui.label("Step 1: Override background").on_hover_text(
+                                    "The most frequent color is used as background \
+                                    if not overridden",
+                                );
+                                let mut override_background =
+                                    self.settings.step1_background.is_some();
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut override_background, "");
+                                    if override_background {
+                                        let background = self
+                                            .settings
+                                            .step1_background
+                                            .get_or_insert([255, 255, 255]);
+                                        egui::color_picker::color_edit_button_srgb(ui, background);
+                                    } else {
+                                        self.settings.step1_background = None;
+                                    }
+                                });
+                            }
+                            ui.end_row();
+                            {
+                                ui.label("Step 1: Monochrome").on_hover_text(
+                                    "Black and gray curves, the frame, ticks and text \
+                                    are removed by their shape",
+                                );
+                                ui.checkbox(&mut self.settings.step1_monochrome, "");
+                            }
+                            ui.end_row();
+                            {
+                                ui.label("Step 1: Detect legend").on_hover_text(
+                                    "Exclude the legend from the curves, \
+                                    its labels are shown next to the names of the curves",
+                                );
+                                ui.checkbox(&mut self.settings.step1_detect_legend, "");
+                            }
+                            ui.end_row();
+                            if self.settings.step1_monochrome {
+                                step2_frame_min_length_fraction.show_and_parse(
+                                    "Step 2: Frame min length",
+                                    "Minimal length of a horizontal or vertical line \
+                                    to be removed as frame\n\
+                                    Fraction of the image width or height, \
+                                    value between 0.0 and 1.0",
+                                    &mut self.settings.step2_frame_min_length_fraction,
+                                    ui,
+                                );
+                                ui.end_row();
+                            }
+                            if self.settings.step1_monochrome || self.settings.step1_detect_legend {
+                                step2_max_symbol_size.show_and_parse(
+                                    "Step 2: Max symbol size",
+                                    "Components up to this size in pixels are removed as text, \
+                                    should be smaller than the dashes of dashed curves\n\
+                                    Also the maximal height of legend text and swatches",
+                                    &mut self.settings.step2_max_symbol_size,
+                                    ui,
+                                );
+                                ui.end_row();
+                            }
                             step1_width_minimial_fraction.show_and_parse(
                                 "Step 1: Minimal Width",
                                 "Minimal allowed fraction of image width \
@@ -331,6 +581,49 @@ impl Tab {
                                 ui,
                             );
                             ui.end_row();
+                            ui.label("Step 2: Color metric").on_hover_text(
+                                "Rgb: sum of channel differences\n\
+                                Cielab: perceptual difference (CIEDE2000), \
+                                a radius of about 2 is just noticeable",
+                            );
+                            egui::ComboBox::from_id_source("color_metric")
+                                .selected_text(format!(
+                                    "{:?}",
+                                    self.settings.step1_step2_color_metric
+                                ))
+                                .show_ui(ui, |ui| {
+                                    for metric in [
+                                        graph_to_data::ColorMetric::Rgb,
+                                        graph_to_data::ColorMetric::Cielab,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut self.settings.step1_step2_color_metric,
+                                            metric,
+                                            format!("{metric:?}"),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+                            ui.label("Step 1: Palette").on_hover_text(
+                                "FirstSeen: a pixel belongs to the first color within the radius\n\
+                                Clustering: colors are the centroids of clusters \
+                                of the color histogram",
+                            );
+                            egui::ComboBox::from_id_source("palette")
+                                .selected_text(format!("{:?}", self.settings.step1_palette))
+                                .show_ui(ui, |ui| {
+                                    for palette in [
+                                        graph_to_data::PaletteMethod::FirstSeen,
+                                        graph_to_data::PaletteMethod::Clustering,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut self.settings.step1_palette,
+                                            palette,
+                                            format!("{palette:?}"),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
                             step3_min_width_fraction.show_and_parse(
                                 "Step 3: Minimal width",
                                 "Minimal width of connected pixels \
@@ -340,6 +633,83 @@ impl Tab {
                                 ui,
                             );
                             ui.end_row();
+                            if self.settings.mode == graph_to_data::DetectionMode::Scatter {
+                                step3_marker_min_pixels.show_and_parse(
+                                    "Step 3: Marker min pixels",
+                                    "Minimal number of pixels of a marker",
+                                    &mut self.settings.step3_marker_min_pixels,
+                                    ui,
+                                );
+                                ui.end_row();
+                                step3_marker_max_size_fraction.show_and_parse(
+                                    "Step 3: Marker max size",
+                                    "Maximal width/height of a marker \
+                            as fraction of the image height.\
+                            Value between 0.0 and 1.0",
+                                    &mut self.settings.step3_marker_max_size_fraction,
+                                    ui,
+                                );
+                                ui.end_row();
+                                step3_marker_min_count.show_and_parse(
+                                    "Step 3: Marker min count",
+                                    "Colors with less markers are not considered graphs",
+                                    &mut self.settings.step3_marker_min_count,
+                                    ui,
+                                );
+                                ui.end_row();
+                            }
+                            if self.settings.mode == graph_to_data::DetectionMode::Bars {
+                                ui.label("Step 3: Bar orientation");
+                                egui::ComboBox::from_id_source("bar_orientation")
+                                    .selected_text(format!(
+                                        "{:?}",
+                                        self.settings.step3_bar_orientation
+                                    ))
+                                    .show_ui(ui, |ui| {
+                                        for orientation in [
+                                            graph_to_data::BarOrientation::Vertical,
+                                            graph_to_data::BarOrientation::Horizontal,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut self.settings.step3_bar_orientation,
+                                                orientation,
+                                                format!("{orientation:?}"),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+                                step3_bar_min_width.show_and_parse(
+                                    "Step 3: Bar min width",
+                                    "Minimal width of a bar in pixels",
+                                    &mut self.settings.step3_bar_min_width,
+                                    ui,
+                                );
+                                ui.end_row();
+                            }
+                            if self.settings.mode != graph_to_data::DetectionMode::Bars {
+                                ui.label("Step 3: Error bars").on_hover_text(
+                                    "Detect vertical error bars of the same color \
+                                    as the curve or marker",
+                                );
+                                ui.checkbox(&mut self.settings.step3_detect_error_bars, "");
+                                ui.end_row();
+                                if self.settings.step3_detect_error_bars {
+                                    step3_error_bar_max_width.show_and_parse(
+                                        "Step 3: Error bar max width",
+                                        "Maximal width of an error bar in pixels",
+                                        &mut self.settings.step3_error_bar_max_width,
+                                        ui,
+                                    );
+                                    ui.end_row();
+                                    step3_error_bar_min_length.show_and_parse(
+                                        "Step 3: Error bar min length",
+                                        "Minimal length of an error bar in pixels",
+                                        &mut self.settings.step3_error_bar_min_length,
+                                        ui,
+                                    );
+                                    ui.end_row();
+                                }
+                            }
                             step4_component_jump_height_fraction.show_and_parse(
                                 "Step 4: Jump height",
                                 "Maximal vertical jump hight allowed to join \
@@ -349,6 +719,47 @@ impl Tab {
                                 ui,
                             );
                             ui.end_row();
+                            if self.settings.mode == graph_to_data::DetectionMode::Lines {
+                                ui.label("Step 4: Bridge dashes").on_hover_text(
+                                    "Join the dashes or dots of a curve and interpolate \
+                                    the gaps between them",
+                                );
+                                ui.checkbox(&mut self.settings.step4_bridge_dashes, "");
+                                ui.end_row();
+                                if self.settings.step4_bridge_dashes {
+                                    step4_max_dash_gap.show_and_parse(
+                                        "Step 4: Max dash gap",
+                                        "Maximal gap between dashes in pixels",
+                                        &mut self.settings.step4_max_dash_gap,
+                                        ui,
+                                    );
+                                    ui.end_row();
+                                }
+                            }
+                            if self.settings.mode == graph_to_data::DetectionMode::Lines {
+                                ui.label("Step 5: Curve output").on_hover_text(
+                                    "Function: one value per x\n\
+                                    Polyline: every vertical run, ordered along the curve",
+                                );
+                                egui::ComboBox::from_id_source("curve_output")
+                                    .selected_text(format!(
+                                        "{:?}",
+                                        self.settings.step5_curve_output
+                                    ))
+                                    .show_ui(ui, |ui| {
+                                        for output in [
+                                            graph_to_data::CurveOutput::Function,
+                                            graph_to_data::CurveOutput::Polyline,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut self.settings.step5_curve_output,
+                                                output,
+                                                format!("{output:?}"),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+                            }
                             {
                                 ui.label("Override fit color");
                                 let mut override_fit_color =
@@ -369,6 +780,170 @@ impl Tab {
                                 });
                             }
                             ui.end_row();
+                            if self.settings.mode == graph_to_data::DetectionMode::Lines {
+                                step6_despike_window.show_and_parse(
+                                    "Step 6: Despike window",
+                                    "Points far from the median of this many points \
+                                    are replaced by the median, 0 to disable",
+                                    &mut self.settings.step6_despike_window,
+                                    ui,
+                                );
+                                ui.end_row();
+                                ui.label("Step 6: Smoothing").on_hover_text(
+                                    "MovingAverage: mean of the window\n\
+                                    SavitzkyGolay: quadratic fit within the window, \
+                                    keeps peaks",
+                                );
+                                egui::ComboBox::from_id_source("smoothing")
+                                    .selected_text(format!("{:?}", self.settings.step6_smoothing))
+                                    .show_ui(ui, |ui| {
+                                        for smoothing in [
+                                            graph_to_data::Smoothing::None,
+                                            graph_to_data::Smoothing::MovingAverage,
+                                            graph_to_data::Smoothing::SavitzkyGolay,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut self.settings.step6_smoothing,
+                                                smoothing,
+                                                format!("{smoothing:?}"),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+                                if self.settings.step6_smoothing != graph_to_data::Smoothing::None {
+                                    step6_smoothing_window.show_and_parse(
+                                        "Step 6: Smoothing window",
+                                        "Number of points, odd",
+                                        &mut self.settings.step6_smoothing_window,
+                                        ui,
+                                    );
+                                    ui.end_row();
+                                }
+                                step6_simplify_tolerance.show_and_parse(
+                                    "Step 6: Simplify tolerance",
+                                    "Points closer than this to the simplified curve \
+                                    are removed, in data units, 0 to disable",
+                                    &mut self.settings.step6_simplify_tolerance,
+                                    ui,
+                                );
+                                ui.end_row();
+                            }
+                            if self.settings.mode != graph_to_data::DetectionMode::Bars {
+                                ui.label("Step 6: Resample").on_hover_text(
+                                    "Export all plots interpolated onto a common x grid",
+                                );
+                                ui.checkbox(&mut self.settings.step6_resample, "");
+                                ui.end_row();
+                            }
+                            if self.settings.mode != graph_to_data::DetectionMode::Bars
+                                && self.settings.step6_resample
+                            {
+                                use graph_to_data::ResampleGrid;
+                                let kind = |grid: &ResampleGrid| match grid {
+                                    ResampleGrid::Count(_) => "Count",
+                                    ResampleGrid::Step(_) => "Step",
+                                    ResampleGrid::Explicit(_) => "Explicit",
+                                };
+                                ui.label("Step 6: Grid").on_hover_text(
+                                    "Count: evenly spaced points over the range of all plots\n\
+                                    Step: fixed distance between the points\n\
+                                    Explicit: list of x values",
+                                );
+                                egui::ComboBox::from_id_source("resample_grid")
+                                    .selected_text(kind(&self.settings.step6_resample_grid))
+                                    .show_ui(ui, |ui| {
+                                        let grids = [
+                                            ResampleGrid::Count(
+                                                u32::parse(&step6_resample_count.current)
+                                                    .unwrap_or(100),
+                                            ),
+                                            ResampleGrid::Step(
+                                                f64::parse(&step6_resample_step.current)
+                                                    .unwrap_or(1.),
+                                            ),
+                                            ResampleGrid::Explicit(
+                                                Vec::parse(&step6_resample_xs.current)
+                                                    .unwrap_or_default(),
+                                            ),
+                                        ];
+                                        for grid in grids {
+                                            let selected = kind(&self.settings.step6_resample_grid)
+                                                == kind(&grid);
+                                            if ui.selectable_label(selected, kind(&grid)).clicked()
+                                                && !selected
+                                            {
+                                                self.settings.step6_resample_grid = grid;
+                                            }
+                                        }
+                                    });
+                                ui.end_row();
+                                match &mut self.settings.step6_resample_grid {
+                                    ResampleGrid::Count(count) => step6_resample_count
+                                        .show_and_parse(
+                                            "Step 6: Points",
+                                            "Number of points of the grid",
+                                            count,
+                                            ui,
+                                        ),
+                                    ResampleGrid::Step(step) => step6_resample_step.show_and_parse(
+                                        "Step 6: Step",
+                                        "Distance between the points of the grid",
+                                        step,
+                                        ui,
+                                    ),
+                                    ResampleGrid::Explicit(xs) => step6_resample_xs.show_and_parse(
+                                        "Step 6: X values",
+                                        "Separated by commas, semicolons or spaces",
+                                        xs,
+                                        ui,
+                                    ),
+                                }
+                                ui.end_row();
+                                ui.label("Step 6: Interpolation");
+                                egui::ComboBox::from_id_source("resample_interpolation")
+                                    .selected_text(format!(
+                                        "{:?}",
+                                        self.settings.step6_resample_interpolation
+                                    ))
+                                    .show_ui(ui, |ui| {
+                                        for interpolation in [
+                                            graph_to_data::Interpolation::Linear,
+                                            graph_to_data::Interpolation::Spline,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut self.settings.step6_resample_interpolation,
+                                                interpolation,
+                                                format!("{interpolation:?}"),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+                                ui.label("Step 6: Outside range").on_hover_text(
+                                    "Value beyond the first or last point of a plot\n\
+                                    Nan: no value\n\
+                                    Nearest: value of the nearest end\n\
+                                    Extrapolate: continued with the slope at the end",
+                                );
+                                egui::ComboBox::from_id_source("resample_outside")
+                                    .selected_text(format!(
+                                        "{:?}",
+                                        self.settings.step6_resample_outside
+                                    ))
+                                    .show_ui(ui, |ui| {
+                                        for outside in [
+                                            graph_to_data::OutsideRange::Nan,
+                                            graph_to_data::OutsideRange::Nearest,
+                                            graph_to_data::OutsideRange::Extrapolate,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut self.settings.step6_resample_outside,
+                                                outside,
+                                                format!("{outside:?}"),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+                            }
                         });
                     if ui.button("Detect").clicked() {
                         self.state = self.detect()
@@ -378,13 +953,15 @@ impl Tab {
                 ui.heading("Axis settings");
 
                 egui::Grid::new("axis_settings_grid")
-                    .num_columns(3)
+                    .num_columns(5)
                     .max_col_width(ui.available_width() / 2.)
                     .show(ui, |ui| {
                         {
                             ui.label(" ");
                             ui.label("Min");
                             ui.label("Max");
+                            ui.label("Scale");
+                            ui.label("Format");
                             ui.end_row();
                         }
 
@@ -393,6 +970,12 @@ impl Tab {
                         self.axis_settings.y_axis.show("Y", ui);
                         ui.end_row();
                     });
+                if let Some(axis) = self.calibration_settings.show(ui, &self.axis_settings) {
+                    self.state = State::PickReference(axis);
+                }
+                if let Some(point) = self.polar_settings.show(ui) {
+                    self.state = State::PickPolar(point);
+                }
 
                 ui.separator();
                 let image = egui::Image::from_texture(egui::load::SizedTexture {
@@ -418,18 +1001,38 @@ impl Tab {
                     Refine,
                     Detect,
                     CropByRectangle(UnitQuadrilateral),
+                    AutoDetected(UnitQuadrilateral),
                 }
                 let mut requested = Request::None;
-                if self.crop_settings.is_set().is_some() {
-                    ui.horizontal(|ui| {
-                        if ui.button("Refine").clicked() {
-                            requested = Request::Refine;
-                        }
-                        if ui.button("Detect").clicked() {
-                            requested = Request::Detect;
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Auto-detect plot area")
+                        .on_hover_text("Search for the axis frame")
+                        .clicked()
+                    {
+                        let image =
+                            ImageBuf::try_from(self.original_image.as_ref().unwrap().0.clone());
+                        if let Some(area) = image
+                            .ok()
+                            .and_then(|image| graph_to_data::detect_plot_area(&image))
+                        {
+                            requested = Request::AutoDetected(area);
+                        } else {
+                            crop_by_rectangle.auto_detect_failed = true;
                         }
-                    });
-                }
+                    }
+                    let crop_set = self.crop_settings.is_set().is_some();
+                    if crop_set && ui.button("Refine").clicked() {
+                        requested = Request::Refine;
+                    }
+                    if (crop_set || self.polar_settings.use_polar) && ui.button("Detect").clicked()
+                    {
+                        requested = Request::Detect;
+                    }
+                    if crop_by_rectangle.auto_detect_failed {
+                        ui.label("No plot area found");
+                    }
+                });
                 let image = egui::Image::from_texture(egui::load::SizedTexture {
                     id: self.original_image.as_ref().unwrap().1.id(),
                     size: ui.available_size_before_wrap(),
@@ -464,6 +1067,10 @@ impl Tab {
                         );
                     }
                 }
+                self.calibration_settings
+                    .paint(&ui.painter().with_clip_rect(response.rect), response.rect);
+                self.polar_settings
+                    .paint(&ui.painter().with_clip_rect(response.rect), response.rect);
                 if let Some(previous) = &crop_by_rectangle.previous_rectangle {
                     let UnitQuadrilateral { lt, lb, rt, rb } = previous;
                     let lerp = |p: &UnitPoint| response.rect.lerp_inside(egui::vec2(p.x.0, p.y.0));
@@ -485,6 +1092,7 @@ impl Tab {
                     Request::CropByRectangle(selected_area) => {
                         Some(Work::CroppedByRectangle(selected_area))
                     }
+                    Request::AutoDetected(area) => Some(Work::AutoDetected(area)),
                 }
             }
             State::LineDetecting(x) => {
@@ -498,11 +1106,22 @@ impl Tab {
             }
             State::LineDetected(result) => {
                 let result: &mut Result<_, _> = &mut *result;
+                let mut redetect = false;
                 match result {
-                    Ok((image, csv)) => {
+                    Ok(Detected {
+                        image,
+                        confidence_image,
+                        show_confidence,
+                        detected,
+                        mode,
+                        names,
+                        stages,
+                        inspected,
+                    }) => {
                         if let Some(image) = image {
                             ui.horizontal(|ui| {
                                 if ui.button("Save csv to file").clicked() {
+                                    let csv = detected_csv(detected, *mode);
                                     #[cfg(not(target_arch = "wasm32"))]
                                     {
                                         let dialog =
@@ -531,6 +1150,7 @@ impl Tab {
                                     }
                                 }
                                 if ui.button("Copy csv to clipboard").clicked() {
+                                    let csv = detected_csv(detected, *mode);
                                     #[cfg(not(target_arch = "wasm32"))]
                                     {
                                         if let Ok(mut clipboard) = arboard::Clipboard::new() {
@@ -549,14 +1169,73 @@ impl Tab {
                                         }
                                     }
                                 }
+                                if confidence_image.is_some() {
+                                    ui.checkbox(show_confidence, "Color by confidence")
+                                        .on_hover_text(
+                                            "Red: low confidence\nGreen: high confidence",
+                                        );
+                                }
                             });
+                            if !names.is_empty() {
+                                egui::CollapsingHeader::new("Names").show(ui, |ui| {
+                                    egui::Grid::new("plot_names_grid").num_columns(2).show(
+                                        ui,
+                                        |ui| {
+                                            for (index, PlotName { name, label }) in
+                                                names.iter_mut().enumerate()
+                                            {
+                                                if let Some(label) = label {
+                                                    ui.image(
+                                                        egui::load::SizedTexture::from_handle(
+                                                            label,
+                                                        ),
+                                                    )
+                                                    .on_hover_text("Label in the legend");
+                                                } else {
+                                                    ui.label("");
+                                                }
+                                                if ui
+                                                    .text_edit_singleline(name)
+                                                    .on_hover_text("Name in the csv header")
+                                                    .changed()
+                                                {
+                                                    detected.set_name(index, name.clone());
+                                                }
+                                                ui.end_row();
+                                            }
+                                        },
+                                    );
+                                });
+                            }
+                            let open = egui::CollapsingHeader::new("Pipeline inspector")
+                                .default_open(self.inspect_pipeline)
+                                .show(ui, |ui| show_pipeline_inspector(ui, stages, inspected))
+                                .body_returned
+                                .is_some();
+                            if !open {
+                                *inspected = None;
+                            }
+                            // the stages of the last detection are missing, detect again
+                            redetect = open && !self.inspect_pipeline && stages.is_empty();
+                            self.inspect_pipeline = open;
+                            let (image, hover_text) = match (
+                                inspected
+                                    .and_then(|index| stages.get(index))
+                                    .and_then(|stage| Some((stage.texture.as_ref()?, &stage.name))),
+                                confidence_image,
+                            ) {
+                                (Some((texture, name)), _) => (texture, name.as_str()),
+                                (None, Some(confidence_image)) if *show_confidence => {
+                                    (&*confidence_image, "Cropped image with detected lines")
+                                }
+                                _ => (&*image, "Cropped image with detected lines"),
+                            };
                             let image = egui::Image::from_texture(egui::load::SizedTexture {
                                 id: image.id(),
                                 size: ui.available_size_before_wrap(),
                             })
                             .sense(egui::Sense::click());
-                            egui::Widget::ui(image, ui)
-                                .on_hover_text("Cropped image with detected lines");
+                            egui::Widget::ui(image, ui).on_hover_text(hover_text);
                         } else {
                             ui.label("Failed to fit curves");
                         }
@@ -569,7 +1248,53 @@ impl Tab {
                         );
                     }
                 }
-                None
+                redetect.then_some(Work::Detect)
+            }
+            State::PickReference(axis) => {
+                ui.heading(format!("Click on a tick mark of the {}-axis", axis.label()));
+                let image = egui::Image::from_texture(egui::load::SizedTexture {
+                    id: self.original_image.as_ref().unwrap().1.id(),
+                    size: ui.available_size_before_wrap(),
+                })
+                .sense(egui::Sense::click());
+                let response =
+                    egui::Widget::ui(image, ui).on_hover_cursor(egui::CursorIcon::Crosshair);
+                self.calibration_settings
+                    .paint(&ui.painter().with_clip_rect(response.rect), response.rect);
+                if let Some(point) = response
+                    .clicked()
+                    .then(|| response.interact_pointer_pos())
+                    .flatten()
+                    .and_then(|pos| position_converter_relative(pos, response.rect))
+                {
+                    self.calibration_settings.add_point(*axis, point);
+                    Some(Work::CropByRectangle)
+                } else {
+                    None
+                }
+            }
+            State::PickPolar(point) => {
+                ui.heading(format!("Click on the {} of the polar axes", point.label()));
+                let image = egui::Image::from_texture(egui::load::SizedTexture {
+                    id: self.original_image.as_ref().unwrap().1.id(),
+                    size: ui.available_size_before_wrap(),
+                })
+                .sense(egui::Sense::click());
+                let response =
+                    egui::Widget::ui(image, ui).on_hover_cursor(egui::CursorIcon::Crosshair);
+                self.polar_settings
+                    .paint(&ui.painter().with_clip_rect(response.rect), response.rect);
+                if let Some(position) = response
+                    .clicked()
+                    .then(|| response.interact_pointer_pos())
+                    .flatten()
+                    .and_then(|pos| position_converter_relative(pos, response.rect))
+                {
+                    self.polar_settings.set_point(*point, position);
+                    Some(Work::CropByRectangle)
+                } else {
+                    None
+                }
             }
             State::RefineCrop(refine) => {
                 ui.horizontal(|ui| {
@@ -665,6 +1390,10 @@ impl Tab {
                     self.crop_settings.set(area);
                     State::RefineCrop(RefineCrop::LeftTop)
                 }
+                Work::AutoDetected(area) => {
+                    self.crop_settings.set(area);
+                    State::CropByRectangle(self.crop_settings.convert())
+                }
                 Work::CropByRectangle => State::CropByRectangle(self.crop_settings.convert()),
                 Work::RefineCrop(refine) => State::RefineCrop(refine),
                 Work::Detect => self.detect(),
@@ -674,22 +1403,46 @@ impl Tab {
 
     #[must_use]
     fn detect(&mut self) -> State {
-        if let Some(crop_area) = self.crop_settings.is_set() {
-            if let Some(axes) = self.axis_settings.is_set() {
-                let image = self.original_image.as_ref().unwrap().0.clone();
-                let settings = self.settings.clone();
+        // the crop is ignored for polar axes
+        let crop_area = self.crop_settings.is_set().or_else(|| {
+            self.polar_settings.use_polar.then(|| {
+                UnitQuadrilateral::rectangular(
+                    UnitPoint::new([0., 0.]).unwrap(),
+                    UnitPoint::new([1., 1.]).unwrap(),
+                )
+            })
+        });
+        if let Some(crop_area) = crop_area {
+            let axes = if self.polar_settings.use_polar {
+                self.polar_settings
+                    .is_set(&self.axis_settings)
+                    .map(graph_to_data::AxesMapping::Polar)
+            } else if self.calibration_settings.use_reference_points {
+                self.calibration_settings
+                    .is_set(&self.axis_settings)
+                    .map(graph_to_data::AxesMapping::Calibration)
+            } else if let Some(axes) = self.axis_settings.is_set() {
+                Ok(axes.mapping())
+            } else {
+                Err("Axes not set".into())
+            };
+            match axes {
+                Ok(axes) => {
+                    let image = self.original_image.as_ref().unwrap().0.clone();
+                    let settings = self.settings.clone();
 
-                let input = crate::tasks::DetectionTaskInput {
-                    image,
-                    settings,
-                    crop_area,
-                    axes,
-                };
-                self.detection_task.task.enqueue(input);
+                    let input = crate::tasks::DetectionTaskInput {
+                        image,
+                        settings,
+                        crop_area,
+                        axes,
+                        debug_images: self.inspect_pipeline,
+                    };
+                    self.detection_task.task.enqueue(input);
 
-                State::LineDetecting(wasm_timer::Instant::now())
-            } else {
-                State::LineDetected(Box::new(Err("Axes not set".into())))
+                    State::LineDetecting(wasm_timer::Instant::now())
+                }
+                Err(error) => State::LineDetected(Box::new(Err(error))),
             }
         } else {
             State::CropByRectangle(self.crop_settings.convert())
@@ -698,6 +1451,7 @@ impl Tab {
 }
 enum Work {
     CroppedByRectangle(UnitQuadrilateral),
+    AutoDetected(UnitQuadrilateral),
     CropByRectangle,
     RefineCrop(RefineCrop),
     Detect,
@@ -706,12 +1460,14 @@ enum Work {
 struct CropByRectangle {
     previous_rectangle: Option<UnitQuadrilateral>,
     drag_start: Option<egui::Pos2>,
+    auto_detect_failed: bool,
 }
 impl CropByRectangle {
     fn with_previous(area: UnitQuadrilateral) -> Self {
         Self {
             previous_rectangle: Some(area),
             drag_start: None,
+            auto_detect_failed: false,
         }
     }
 
@@ -719,6 +1475,7 @@ impl CropByRectangle {
         Self {
             previous_rectangle: None,
             drag_start: None,
+            auto_detect_failed: false,
         }
     }
 }
@@ -730,8 +1487,82 @@ enum State {
     LineDetecting(wasm_timer::Instant),
     LineDetected(Box<DetectResult>),
     RefineCrop(RefineCrop),
+    PickReference(ReferenceAxis),
+    PickPolar(PolarPoint),
+}
+type DetectResult = Result<Detected, String>;
+struct Detected {
+    image: Option<egui::TextureHandle>,
+    confidence_image: Option<egui::TextureHandle>,
+    show_confidence: bool,
+    detected: graph_to_data::LineDetected,
+    mode: graph_to_data::DetectionMode,
+    names: Vec<PlotName>,
+    stages: Vec<Stage>,
+    /// Index of the stage shown instead of the detected plots
+    inspected: Option<usize>,
+}
+/// Intermediate image of the detection for tuning the settings
+struct Stage {
+    name: String,
+    color: Option<[u8; 3]>,
+    texture: Option<egui::TextureHandle>,
+}
+/// Selection of a stage per color, the selected stage is shown instead of the detected plots
+fn show_pipeline_inspector(ui: &mut egui::Ui, stages: &[Stage], inspected: &mut Option<usize>) {
+    let mut colors = Vec::new();
+    for stage in stages {
+        if !colors.contains(&stage.color) {
+            colors.push(stage.color);
+        }
+    }
+    egui::Grid::new("pipeline_inspector_grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            for color in colors {
+                if let Some([r, g, b]) = color {
+                    egui::color_picker::show_color(
+                        ui,
+                        egui::Color32::from_rgb(r, g, b),
+                        egui::vec2(ui.spacing().interact_size.y, ui.spacing().interact_size.y),
+                    )
+                    .on_hover_text(format!("#{r:02X}{g:02X}{b:02X}"));
+                } else {
+                    ui.label("All colors");
+                }
+                ui.horizontal_wrapped(|ui| {
+                    for (index, stage) in stages
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, stage)| stage.color == color)
+                    {
+                        let selected = *inspected == Some(index);
+                        if ui.selectable_label(selected, &stage.name).clicked() {
+                            *inspected = (!selected).then_some(index);
+                        }
+                    }
+                });
+                ui.end_row();
+            }
+        });
+}
+/// Name of a detected plot for the csv header, with its label in the legend if found
+struct PlotName {
+    /// Edited name, also set on the detection
+    name: String,
+    label: Option<egui::TextureHandle>,
+}
+fn detected_csv(
+    detected: &graph_to_data::LineDetected,
+    mode: graph_to_data::DetectionMode,
+) -> String {
+    match mode {
+        graph_to_data::DetectionMode::Bars => detected.bars_as_csv(),
+        graph_to_data::DetectionMode::Lines | graph_to_data::DetectionMode::Scatter => {
+            detected.as_csv()
+        }
+    }
 }
-type DetectResult = Result<(Option<egui::TextureHandle>, String), String>;
 #[derive(Default)]
 enum RefineCrop {
     #[default]
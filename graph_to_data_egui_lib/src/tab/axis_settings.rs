@@ -1,46 +1,199 @@
+use graph_to_data::{AxesMapping, Axis, AxisFormat, AxisScale, TimeUnit};
+
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct AxisDimension {
-    fraction: Option<f32>,
+    fraction: Option<f64>,
     before: String,
     current: String,
     is_parse_error: Option<String>,
+    /// Format of the last parse, the text is parsed again if it changes
+    #[serde(skip)]
+    format: AxisFormat,
 }
 impl AxisDimension {
-    fn show(&mut self, ui: &mut egui::Ui) {
+    pub(super) fn show(&mut self, ui: &mut egui::Ui, invalid: Option<&str>, format: AxisFormat) {
         ui.scope(|ui| {
-            if self.is_parse_error.is_some() {
+            if self.is_parse_error.is_some() || invalid.is_some() {
                 ui.style_mut().visuals.extreme_bg_color = egui::Color32::RED;
                 ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
             } else if self.current.is_empty() {
                 ui.style_mut().visuals.extreme_bg_color = egui::Color32::YELLOW;
                 ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
             }
-            ui.text_edit_singleline(&mut self.current);
+            let response = ui.text_edit_singleline(&mut self.current);
+            if let Some(error) = self.is_parse_error.as_deref().or(invalid) {
+                response.on_hover_text(error);
+            }
         });
 
-        if self.current != self.before {
+        if self.current != self.before || self.format != format {
             self.before.clone_from(&self.current);
+            self.format = format;
             if self.current.trim().is_empty() {
                 self.fraction = None;
                 self.is_parse_error = None;
             } else {
-                match self.current.parse::<f32>() {
+                match format.parse(&self.current) {
                     Ok(v) => {
                         self.fraction = Some(v);
                         self.is_parse_error = None;
                     }
-                    Err(e) => self.is_parse_error = Some(e.to_string()),
+                    Err(e) => self.is_parse_error = Some(e),
                 }
             }
         }
     }
 
-    fn new(x: f32) -> Self {
+    pub(super) fn new(x: f64) -> Self {
         Self {
             fraction: Some(x),
             before: x.to_string(),
             current: x.to_string(),
             is_parse_error: None,
+            format: AxisFormat::Number,
+        }
+    }
+
+    pub(super) fn value(&self) -> Option<f64> {
+        self.fraction
+    }
+}
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+enum ScaleKind {
+    #[default]
+    Linear,
+    Log10,
+    Ln,
+    #[strum(to_string = "Log (base)")]
+    Log,
+}
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AxisScaleSettings {
+    kind: ScaleKind,
+    base: AxisDimension,
+}
+impl AxisScaleSettings {
+    fn show(&mut self, label: &str, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source(("axis_scale", label))
+                .selected_text(self.kind.to_string())
+                .show_ui(ui, |ui| {
+                    for kind in <ScaleKind as strum::IntoEnumIterator>::iter() {
+                        ui.selectable_value(&mut self.kind, kind, kind.to_string());
+                    }
+                });
+            if self.kind == ScaleKind::Log {
+                let invalid = self
+                    .base
+                    .fraction
+                    .is_some_and(|base| base <= 0. || base == 1.)
+                    .then_some("Base has to be positive and different from 1");
+                self.base.show(ui, invalid, AxisFormat::Number);
+            }
+        });
+    }
+
+    fn is_set(&self) -> Option<AxisScale> {
+        match self.kind {
+            ScaleKind::Linear => Some(AxisScale::Linear),
+            ScaleKind::Log10 => Some(AxisScale::Log10),
+            ScaleKind::Ln => Some(AxisScale::Ln),
+            ScaleKind::Log => self.base.fraction.map(|base| AxisScale::Log { base }),
+        }
+    }
+}
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+enum FormatKind {
+    #[default]
+    Number,
+    #[strum(to_string = "Date/time")]
+    Time,
+}
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AxisFormatSettings {
+    kind: FormatKind,
+    unit: TimeUnit,
+    /// Hours
+    utc_offset: AxisDimension,
+}
+impl Default for AxisFormatSettings {
+    fn default() -> Self {
+        Self {
+            kind: FormatKind::Number,
+            unit: TimeUnit::Days,
+            utc_offset: AxisDimension::new(0.),
+        }
+    }
+}
+impl AxisFormatSettings {
+    fn show(&mut self, label: &str, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source(("axis_format", label))
+                .selected_text(self.kind.to_string())
+                .show_ui(ui, |ui| {
+                    for kind in <FormatKind as strum::IntoEnumIterator>::iter() {
+                        ui.selectable_value(&mut self.kind, kind, kind.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Date/time: limits are entered as ISO 8601 dates or timestamps, \
+                    e.g. 2024-03-01 or 2024-03-01T12:00:00+01:00",
+                );
+            if self.kind == FormatKind::Time {
+                egui::ComboBox::from_id_source(("axis_time_unit", label))
+                    .selected_text(format!("{:?}", self.unit))
+                    .show_ui(ui, |ui| {
+                        for unit in [
+                            TimeUnit::Milliseconds,
+                            TimeUnit::Seconds,
+                            TimeUnit::Minutes,
+                            TimeUnit::Hours,
+                            TimeUnit::Days,
+                        ] {
+                            ui.selectable_value(&mut self.unit, unit, format!("{unit:?}"));
+                        }
+                    })
+                    .response
+                    .on_hover_text("Unit of the values since 1970-01-01");
+                ui.label("UTC+").on_hover_text(
+                    "Timezone offset in hours, used for timestamps without an offset \
+                    and for the csv",
+                );
+                self.utc_offset.show(ui, None, AxisFormat::Number);
+            }
+        });
+    }
+
+    fn is_set(&self) -> AxisFormat {
+        match self.kind {
+            FormatKind::Number => AxisFormat::Number,
+            FormatKind::Time => AxisFormat::Time {
+                unit: self.unit,
+                utc_offset_minutes: (self.utc_offset.value().unwrap_or(0.) * 60.).round() as i32,
+            },
         }
     }
 }
@@ -48,24 +201,55 @@ impl AxisDimension {
 pub struct AxisPoint {
     x: AxisDimension,
     y: AxisDimension,
+    #[serde(default)]
+    scale: AxisScaleSettings,
+    #[serde(default)]
+    format: AxisFormatSettings,
 }
 impl AxisPoint {
     pub fn show(&mut self, label: &str, ui: &mut egui::Ui) {
         ui.label(label);
-        self.x.show(ui);
-        self.y.show(ui);
+        let require_positive = self.scale.kind != ScaleKind::Linear;
+        let invalid = |dimension: &AxisDimension| {
+            (require_positive && dimension.fraction.is_some_and(|v| v <= 0.))
+                .then_some("Logarithmic axes require positive limits")
+        };
+        let invalid_x = invalid(&self.x);
+        let invalid_y = invalid(&self.y);
+        let format = self.format();
+        self.x.show(ui, invalid_x, format);
+        self.y.show(ui, invalid_y, format);
+        self.scale.show(label, ui);
+        self.format.show(label, ui);
+    }
+
+    pub fn scale(&self) -> Option<AxisScale> {
+        self.scale.is_set()
+    }
+
+    pub fn format(&self) -> AxisFormat {
+        self.format.is_set()
     }
 
     pub fn is_set(&self) -> Option<Axis> {
-        self.x
-            .fraction
-            .and_then(|min| self.y.fraction.map(|max| Axis { min, max }))
+        let min = self.x.fraction?;
+        let max = self.y.fraction?;
+        let scale = self.scale.is_set()?;
+        let axis = Axis {
+            min,
+            max,
+            scale,
+            format: self.format(),
+        };
+        axis.check().ok().map(|()| axis)
     }
 
-    fn new(x: f32, y: f32) -> Self {
+    fn new(x: f64, y: f64) -> Self {
         Self {
             x: AxisDimension::new(x),
             y: AxisDimension::new(y),
+            scale: Default::default(),
+            format: Default::default(),
         }
     }
 }
@@ -94,26 +278,15 @@ impl AxisSettings {
     }
 }
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
-pub struct Axis {
-    min: f32,
-    max: f32,
-}
-impl Axis {
-    fn limits(&self) -> (f32, f32) {
-        (self.min, self.max)
-    }
-}
-#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
 pub struct Axes {
     x_axis: Axis,
     y_axis: Axis,
 }
 impl Axes {
-    pub(crate) fn x_limits(&self) -> (f32, f32) {
-        self.x_axis.limits()
-    }
-
-    pub(crate) fn y_limits(&self) -> (f32, f32) {
-        self.y_axis.limits()
+    pub(crate) fn mapping(&self) -> AxesMapping {
+        AxesMapping::Limits {
+            x_axis: self.x_axis,
+            y_axis: self.y_axis,
+        }
     }
 }
@@ -0,0 +1,158 @@
+use graph_to_data::{AxisCalibration, Calibration, ReferencePoint, UnitPoint};
+
+use super::axis_settings::{AxisDimension, AxisPoint, AxisSettings};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReferenceAxis {
+    X,
+    Y,
+}
+impl ReferenceAxis {
+    pub fn label(&self) -> &str {
+        match self {
+            ReferenceAxis::X => "X",
+            ReferenceAxis::Y => "Y",
+        }
+    }
+
+    fn color(&self) -> egui::Color32 {
+        match self {
+            ReferenceAxis::X => egui::Color32::RED,
+            ReferenceAxis::Y => egui::Color32::BLUE,
+        }
+    }
+}
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferencePointSettings {
+    position: UnitPoint,
+    value: AxisDimension,
+}
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CalibrationSettings {
+    pub use_reference_points: bool,
+    x_points: Vec<ReferencePointSettings>,
+    y_points: Vec<ReferencePointSettings>,
+}
+impl CalibrationSettings {
+    fn points_mut(&mut self, axis: ReferenceAxis) -> &mut Vec<ReferencePointSettings> {
+        match axis {
+            ReferenceAxis::X => &mut self.x_points,
+            ReferenceAxis::Y => &mut self.y_points,
+        }
+    }
+
+    pub fn add_point(&mut self, axis: ReferenceAxis, position: UnitPoint) {
+        self.points_mut(axis).push(ReferencePointSettings {
+            position,
+            value: Default::default(),
+        });
+    }
+
+    /// Returns the axis for which a new reference point shall be picked
+    #[must_use]
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        axis_settings: &AxisSettings,
+    ) -> Option<ReferenceAxis> {
+        ui.checkbox(&mut self.use_reference_points, "Use reference points")
+            .on_hover_text(
+                "Calibrate the axes by tick marks with known values \
+                instead of the crop corners",
+            );
+        if !self.use_reference_points {
+            return None;
+        }
+        let mut requested = None;
+        for (axis, format) in [
+            (ReferenceAxis::X, axis_settings.x_axis.format()),
+            (ReferenceAxis::Y, axis_settings.y_axis.format()),
+        ] {
+            let points = self.points_mut(axis);
+            let mut removed = None;
+            egui::Grid::new(("reference_points_grid", axis.label()))
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for (index, point) in points.iter_mut().enumerate() {
+                        let UnitPoint { x, y } = point.position;
+                        ui.label(format!("{} #{}", axis.label(), index + 1))
+                            .on_hover_text(format!("Position: ({:.3}, {:.3})", x.0, y.0));
+                        point.value.show(ui, None, format);
+                        if ui.button("🗑").on_hover_text("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(index) = removed {
+                points.remove(index);
+            }
+            if ui
+                .button(format!("Add {} tick", axis.label()))
+                .on_hover_text("Click on a tick mark in the image, then enter its value")
+                .clicked()
+            {
+                requested = Some(axis);
+            }
+        }
+        requested
+    }
+
+    pub fn paint(&self, painter: &egui::Painter, rect: egui::Rect) {
+        if !self.use_reference_points {
+            return;
+        }
+        for (axis, points) in [
+            (ReferenceAxis::X, &self.x_points),
+            (ReferenceAxis::Y, &self.y_points),
+        ] {
+            for (index, point) in points.iter().enumerate() {
+                let UnitPoint { x, y } = point.position;
+                let center = rect.lerp_inside(egui::vec2(x.0, y.0));
+                painter.circle_stroke(center, 5., egui::Stroke::new(2., axis.color()));
+                painter.text(
+                    center + egui::vec2(6., 6.),
+                    egui::Align2::LEFT_TOP,
+                    format!("{} #{}", axis.label(), index + 1),
+                    egui::FontId::default(),
+                    axis.color(),
+                );
+            }
+        }
+    }
+
+    pub fn is_set(&self, axis_settings: &AxisSettings) -> Result<Calibration, String> {
+        let convert = |axis: ReferenceAxis,
+                       points: &[ReferencePointSettings],
+                       axis_point: &AxisPoint|
+         -> Result<AxisCalibration, String> {
+            let scale = axis_point
+                .scale()
+                .ok_or_else(|| format!("{}-axis scale not set", axis.label()))?;
+            let points = points
+                .iter()
+                .enumerate()
+                .map(|(index, point)| {
+                    point
+                        .value
+                        .value()
+                        .map(|value| ReferencePoint {
+                            position: point.position,
+                            value,
+                        })
+                        .ok_or_else(|| format!("Value of {} #{} not set", axis.label(), index + 1))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(AxisCalibration {
+                scale,
+                points,
+                format: axis_point.format(),
+            })
+        };
+        Ok(Calibration {
+            x_axis: convert(ReferenceAxis::X, &self.x_points, &axis_settings.x_axis)?,
+            y_axis: convert(ReferenceAxis::Y, &self.y_points, &axis_settings.y_axis)?,
+        })
+    }
+}
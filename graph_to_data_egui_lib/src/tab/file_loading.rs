@@ -95,10 +95,10 @@ impl FileState {
             }
             LoadingFromBytes => {
                 if let Some(result) = self.load_from_bytes.task.check() {
-                    match result {
+                    match result.and_then(super::ImageBuf::try_from) {
                         Ok(image) => {
                             self.title = Some(self.file_name.as_ref().unwrap().clone());
-                            Loaded(Some(image.into()))
+                            Loaded(Some(image))
                         }
                         Err(e) => {
                             self.title =
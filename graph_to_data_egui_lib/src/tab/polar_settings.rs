@@ -0,0 +1,135 @@
+use graph_to_data::{AngleDirection, AxisFormat, PolarCalibration, UnitPoint};
+
+use super::axis_settings::{AxisDimension, AxisSettings};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolarPoint {
+    Center,
+    Reference,
+}
+impl PolarPoint {
+    pub fn label(&self) -> &str {
+        match self {
+            PolarPoint::Center => "center",
+            PolarPoint::Reference => "reference circle",
+        }
+    }
+}
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PolarSettings {
+    pub use_polar: bool,
+    center: Option<UnitPoint>,
+    reference: Option<UnitPoint>,
+    zero_angle: AxisDimension,
+    direction: AngleDirection,
+}
+impl Default for PolarSettings {
+    fn default() -> Self {
+        Self {
+            use_polar: false,
+            center: None,
+            reference: None,
+            zero_angle: AxisDimension::new(0.),
+            direction: AngleDirection::CounterClockwise,
+        }
+    }
+}
+impl PolarSettings {
+    pub fn set_point(&mut self, point: PolarPoint, position: UnitPoint) {
+        match point {
+            PolarPoint::Center => self.center = Some(position),
+            PolarPoint::Reference => self.reference = Some(position),
+        }
+    }
+
+    /// Returns the point which shall be picked
+    #[must_use]
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<PolarPoint> {
+        ui.checkbox(&mut self.use_polar, "Polar axes")
+            .on_hover_text(
+                "Unwrap the image around the center, the crop is not used\n\
+            The Y limits are the radius at the center and at the reference circle",
+            );
+        if !self.use_polar {
+            return None;
+        }
+        let mut requested = None;
+        egui::Grid::new("polar_settings_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (point, position) in [
+                    (PolarPoint::Center, self.center),
+                    (PolarPoint::Reference, self.reference),
+                ] {
+                    let text = match position {
+                        Some(UnitPoint { x, y }) => format!("({:.3}, {:.3})", x.0, y.0),
+                        None => "Not set".into(),
+                    };
+                    if ui
+                        .button(format!("Pick {}", point.label()))
+                        .on_hover_text("Click on the point in the image")
+                        .clicked()
+                    {
+                        requested = Some(point);
+                    }
+                    ui.label(text);
+                    ui.end_row();
+                }
+                ui.label("Zero angle").on_hover_text(
+                    "Direction of 0° in degrees, counterclockwise from the right, \
+                    e.g. 90 if 0° is at the top",
+                );
+                self.zero_angle.show(ui, None, AxisFormat::Number);
+                ui.end_row();
+                ui.label("Direction");
+                egui::ComboBox::from_id_source("polar_direction")
+                    .selected_text(format!("{:?}", self.direction))
+                    .show_ui(ui, |ui| {
+                        for direction in
+                            [AngleDirection::CounterClockwise, AngleDirection::Clockwise]
+                        {
+                            ui.selectable_value(
+                                &mut self.direction,
+                                direction,
+                                format!("{direction:?}"),
+                            );
+                        }
+                    });
+                ui.end_row();
+            });
+        requested
+    }
+
+    pub fn paint(&self, painter: &egui::Painter, rect: egui::Rect) {
+        if !self.use_polar {
+            return;
+        }
+        let color = egui::Color32::DARK_GREEN;
+        let lerp = |UnitPoint { x, y }: UnitPoint| rect.lerp_inside(egui::vec2(x.0, y.0));
+        if let Some(center) = self.center.map(lerp) {
+            painter.circle_stroke(center, 5., egui::Stroke::new(2., color));
+            if let Some(reference) = self.reference.map(lerp) {
+                painter.line_segment([center, reference], egui::Stroke::new(2., color));
+                painter.circle_stroke(
+                    center,
+                    center.distance(reference),
+                    egui::Stroke::new(1., color),
+                );
+            }
+        }
+    }
+
+    pub fn is_set(&self, axis_settings: &AxisSettings) -> Result<PolarCalibration, String> {
+        Ok(PolarCalibration {
+            center: self.center.ok_or("Polar center not set")?,
+            reference: self.reference.ok_or("Polar reference circle not set")?,
+            radius: axis_settings
+                .y_axis
+                .is_set()
+                .ok_or("Y limits (radius) not set")?,
+            zero_angle: self.zero_angle.value().ok_or("Zero angle not set")? as f32,
+            direction: self.direction,
+        })
+    }
+}
@@ -2,4 +2,4 @@ mod load_from_bytes_task;
 pub use load_from_bytes_task::{ImageSerde, LoadFromBytesTask};
 
 mod detect;
-pub use detect::{DetectionTask, DetectionTaskInput};
+pub use detect::{DetectionTask, DetectionTaskInput, DetectionTaskOutput, StageImage};
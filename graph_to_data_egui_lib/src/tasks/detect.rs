@@ -3,14 +3,36 @@ pub struct DetectionTaskInput {
     pub image: super::ImageSerde,
     pub settings: graph_to_data::Settings,
     pub crop_area: graph_to_data::UnitQuadrilateral,
-    pub axes: crate::tab::Axes,
+    pub axes: graph_to_data::AxesMapping,
+    /// Whether the intermediate images are returned as stages
+    pub debug_images: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DetectionTaskOutput {
+    pub image: Option<super::ImageSerde>,
+    /// Curves colored by their confidence
+    pub confidence_image: Option<super::ImageSerde>,
+    /// Results without the images, exported after renaming the plots
+    pub detected: graph_to_data::LineDetected,
+    pub mode: graph_to_data::DetectionMode,
+    /// Name and legend label per plot
+    pub names: Vec<(String, Option<super::ImageSerde>)>,
+    pub stages: Vec<StageImage>,
+}
+/// Intermediate image of the detection, e.g. the color filtered image of one color
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct StageImage {
+    pub name: String,
+    /// None if the image covers all colors
+    pub color: Option<[u8; 3]>,
+    pub image: super::ImageSerde,
 }
 #[derive(Default)]
 pub struct DetectionTask;
 impl task_simple::Function for DetectionTask {
     type Input = DetectionTaskInput;
 
-    type Output = Result<(Option<super::ImageSerde>, String), String>;
+    type Output = Result<DetectionTaskOutput, String>;
 
     fn call(&mut self, input: Self::Input) -> Self::Output {
         let DetectionTaskInput {
@@ -18,24 +40,49 @@ impl task_simple::Function for DetectionTask {
             settings,
             crop_area,
             axes,
+            debug_images,
         } = input;
-        let image: crate::ImageBuf = image.into();
+        let image: crate::ImageBuf = image.try_into()?;
         let cropped = crop_area.transform([image.width(), image.height()]);
+        let mode = settings.mode;
         graph_to_data::line_detection(
             &image,
             &settings,
             crop_area,
             cropped.width(),
             cropped.height(),
-            axes.x_limits(),
-            axes.y_limits(),
+            &axes,
         )
-        .map_err(|e| format!("{e:?}"))
-        .map(|l| {
-            (
-                l.final_image_with_plots().map(|x| x.clone().into()),
-                l.as_csv(),
-            )
-        })        
+        .map_err(|e| e.to_string())
+        .map(|l| DetectionTaskOutput {
+            image: l.final_image_with_plots().map(|x| x.clone().into()),
+            confidence_image: l.final_image_with_confidence().map(|x| x.clone().into()),
+            names: l
+                .names()
+                .into_iter()
+                .zip(l.legend_entries())
+                .map(|(name, entry)| (name.to_string(), entry.map(|e| e.label.clone().into())))
+                .collect(),
+            stages: if debug_images {
+                l.debug_images()
+                    .into_iter()
+                    .map(|debug_image| StageImage {
+                        name: format!(
+                            "{} {}",
+                            debug_image.step,
+                            debug_image.name.replace('_', " ")
+                        ),
+                        color: debug_image
+                            .color
+                            .map(|color| [color[0], color[1], color[2]]),
+                        image: debug_image.image.to_rgba().into(),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            detected: l,
+            mode,
+        })
     }
 }
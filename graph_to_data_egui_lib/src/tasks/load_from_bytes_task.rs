@@ -28,13 +28,18 @@ impl From<crate::ImageBuf> for ImageSerde {
         }
     }
 }
-impl From<ImageSerde> for crate::ImageBuf {
-    fn from(value: ImageSerde) -> Self {
+impl TryFrom<ImageSerde> for crate::ImageBuf {
+    type Error = String;
+
+    fn try_from(value: ImageSerde) -> Result<Self, Self::Error> {
         let ImageSerde {
             width,
             height,
             bytes,
         } = value;
-        Self::from_vec(width, height, bytes).expect("Failed to convert ImageSerde to ImageBuffer")
+        let length = bytes.len();
+        Self::from_vec(width, height, bytes).ok_or_else(|| {
+            format!("{length} bytes do not match an RGBA image of {width}x{height} pixels")
+        })
     }
 }